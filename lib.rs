@@ -1,26 +1,134 @@
 #![cfg_attr(not(feature = "std"), no_std)]
 
+mod uts46;
+
 #[ink::contract]
 mod dns_contract {
 
-    use ink::prelude::{string::String, vec::Vec};
+    use ink::env::call::{build_call, ExecutionInput, Selector};
+    use ink::env::hash::Blake2x256;
+    use ink::env::DefaultEnvironment;
+    use ink::prelude::{format, string::String, vec::Vec};
     use ink::storage::Mapping;
 
+    use crate::uts46::{COMBINING_CLASSES, VALID_RANGES};
+
     // type for domain id
     pub type DomainNameId = i32;
 
+    // type for bundle id
+    pub type BundleId = i32;
+
+    // key of a text record: name, controller that set it and record key
+    pub type RecordKey = (DomainNameId, AccountId, String);
+
     // offer state for domain name
     #[derive(Debug, scale::Decode, scale::Encode, Eq, PartialEq)]
     #[cfg_attr(
         feature = "std",
         derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
     )]
+    #[allow(clippy::enum_variant_names)]
     pub enum State {
         NotOffering,
-        PrivateOffering,
+        // only the designated buyer may purchase
+        PrivateOffering(AccountId),
         PublicOffering,
+        // price decays from start to floor price over a block range
+        DutchOffering(DutchListing),
+    }
+
+    // how the price of a dutch listing decays
+    #[derive(Debug, Clone, Copy, scale::Decode, scale::Encode, Eq, PartialEq)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub enum PriceCurve {
+        Linear,
+        // price above the floor halves every half_life blocks
+        Exponential { half_life: BlockNumber },
+    }
+
+    // descending price listing
+    #[derive(Debug, Clone, scale::Decode, scale::Encode, Eq, PartialEq)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct DutchListing {
+        start_price: u128,
+        floor_price: u128,
+        start_block: BlockNumber,
+        end_block: BlockNumber,
+        curve: PriceCurve,
+    }
+
+    impl DutchListing {
+        // price of the listing at the given block
+        fn price_at(&self, block: BlockNumber) -> u128 {
+            if block >= self.end_block {
+                return self.floor_price;
+            }
+            let elapsed = block.saturating_sub(self.start_block) as u128;
+            let premium = self.start_price - self.floor_price;
+
+            let remaining = match self.curve {
+                PriceCurve::Linear => {
+                    let duration = (self.end_block - self.start_block) as u128;
+                    premium - mul_div(premium, elapsed, duration)
+                }
+                PriceCurve::Exponential { half_life } => {
+                    let half_life = half_life as u128;
+                    let halvings = elapsed / half_life;
+                    if halvings >= 128 {
+                        0
+                    } else {
+                        // interpolate linearly within the current half life
+                        let step = premium >> halvings;
+                        step - mul_div(step / 2, elapsed % half_life, half_life)
+                    }
+                }
+            };
+            self.floor_price + remaining
+        }
+    }
+
+    // value * numerator / denominator for numerator < denominator, without
+    // overflowing on large values
+    fn mul_div(value: u128, numerator: u128, denominator: u128) -> u128 {
+        value / denominator * numerator + value % denominator * numerator / denominator
+    }
+
+    // currency a listing is priced in
+    #[derive(Debug, Clone, Copy, Default, scale::Decode, scale::Encode, Eq, PartialEq)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub enum Currency {
+        #[default]
+        Native,
+        // PSP22 token contract
+        Psp22(AccountId),
+    }
+
+    // error returned by PSP22 token contracts
+    #[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(::scale_info::TypeInfo))]
+    pub enum PSP22Error {
+        Custom(String),
+        InsufficientBalance,
+        InsufficientAllowance,
+        ZeroRecipientAddress,
+        ZeroSenderAddress,
+        SafeTransferCheckFailed(String),
     }
 
+    // PSP22 message selectors
+    const PSP22_TRANSFER: [u8; 4] = [0xdb, 0x20, 0xf9, 0xf5];
+    const PSP22_TRANSFER_FROM: [u8; 4] = [0x54, 0xb3, 0xc7, 0x6e];
+
     // struct for domain name
     #[derive(Debug, scale::Decode, scale::Encode, Eq, PartialEq)]
     #[cfg_attr(
@@ -31,7 +139,17 @@ mod dns_contract {
         name: String,
         offer_state: State,
         offer_price: u128,
+        offer_currency: Currency,
+        // listing lapses at this time, None never lapses
+        offer_expires_at: Option<Timestamp>,
         default_address: AccountId,
+        // registration lapses at this time unless renewed
+        expires_at: Timestamp,
+        // first registrant, receives royalties on secondary sales
+        registrant: AccountId,
+        royalty_bps: u16,
+        // name this is a subdomain of, None for names registered under a tld
+        parent: Option<DomainNameId>,
     }
 
     // Default implementation for Domain name
@@ -41,16 +159,531 @@ mod dns_contract {
                 name: Default::default(),
                 offer_state: State::NotOffering,
                 offer_price: Default::default(),
+                offer_currency: Currency::Native,
+                offer_expires_at: None,
                 default_address: zero_address(),
+                expires_at: 0,
+                registrant: zero_address(),
+                royalty_bps: 0,
+                parent: None,
             }
         }
     }
 
+    impl DomainName {
+        // take the domain name off the market
+        fn clear_offer(&mut self) {
+            self.offer_state = State::NotOffering;
+            self.offer_price = 0;
+            self.offer_currency = Currency::Native;
+            self.offer_expires_at = None;
+        }
+
+        fn is_expired(&self, now: Timestamp) -> bool {
+            now >= self.expires_at
+        }
+
+        fn offer_lapsed(&self, now: Timestamp) -> bool {
+            self.offer_state != State::NotOffering
+                && self.offer_expires_at.is_some_and(|expiry| now >= expiry)
+        }
+    }
+
+    // terms under which anyone can claim a subdomain of a name
+    #[derive(Debug, Clone, scale::Decode, scale::Encode, Eq, PartialEq)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct SubdomainRegistrar {
+        price: u128,
+        // registration term of a claimed subdomain, capped at the parent expiry
+        duration: Timestamp,
+        // allowed label length in characters
+        min_length: u32,
+        max_length: u32,
+    }
+
+    // registration policy of a top-level domain
+    #[derive(Debug, Clone, scale::Decode, scale::Encode, Eq, PartialEq)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct TldPolicy {
+        // yearly price by label length, None uses the contract price schedule
+        price_schedule: Option<Vec<u128>>,
+        // allowed label length in characters
+        min_length: u32,
+        max_length: u32,
+        open: bool,
+        // account allowed to change the policy besides the contract owner
+        manager: Option<AccountId>,
+    }
+
+    // lifecycle phase of a registration
+    #[derive(Debug, Clone, Copy, scale::Decode, scale::Encode, Eq, PartialEq)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum NamePhase {
+        Active,
+        // expired, only the previous owner may renew
+        GracePeriod,
+        // only the previous owner may renew, paying the redemption fee
+        Redemption,
+        // released, anyone may register the name again
+        Available,
+    }
+
+    // english auction running on a domain name
+    #[derive(Debug, scale::Decode, scale::Encode, Eq, PartialEq)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct Auction {
+        seller: AccountId,
+        reserve_price: u128,
+        end_time: Timestamp,
+        highest_bidder: Option<AccountId>,
+        highest_bid: u128,
+    }
+
+    // bids placed this close to the end push the end time back (10 minutes)
+    const AUCTION_EXTENSION: Timestamp = 10 * 60 * 1000;
+
+    // sealed-bid (vickrey) auction for a name that is not registered yet
+    #[derive(Debug, scale::Decode, scale::Encode, Eq, PartialEq)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct SealedAuction {
+        commit_end: Timestamp,
+        reveal_end: Timestamp,
+        highest_bidder: Option<AccountId>,
+        highest_bid: u128,
+        highest_deposit: u128,
+        second_bid: u128,
+    }
+
+    // committed hash of (name, bidder, bid, salt) with the escrowed deposit
+    #[derive(Debug, scale::Decode, scale::Encode, Eq, PartialEq)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct SealedBid {
+        hash: Hash,
+        deposit: u128,
+    }
+
+    // length of the commit and reveal phases of a sealed auction (2 days each)
+    const SEALED_COMMIT_PERIOD: Timestamp = 2 * 24 * 60 * 60 * 1000;
+    const SEALED_REVEAL_PERIOD: Timestamp = 2 * 24 * 60 * 60 * 1000;
+
+    // escrowed offer on a domain name whether or not it is listed
+    #[derive(Debug, Clone, scale::Decode, scale::Encode, Eq, PartialEq)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct StandingBid {
+        bidder: AccountId,
+        amount: u128,
+        expires_at: Timestamp,
+    }
+
+    // max outstanding standing bids per domain name
+    const MAX_STANDING_BIDS: usize = 50;
+
+    // private price negotiation between a buyer and the owner of a name
+    #[derive(Debug, scale::Decode, scale::Encode, Eq, PartialEq)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct Negotiation {
+        // price proposed and escrowed by the buyer
+        escrow: u128,
+        // latest counter price from the owner
+        counter_price: Option<u128>,
+        // owner that made the counter offer, it lapses when the name changes hands
+        countered_by: Option<AccountId>,
+    }
+
+    // several domain names of one seller sold together for a single price
+    #[derive(Debug, scale::Decode, scale::Encode, Eq, PartialEq)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct Bundle {
+        seller: AccountId,
+        name_ids: Vec<DomainNameId>,
+        price: u128,
+    }
+
+    // max domain names in a bundle
+    const MAX_BUNDLE_SIZE: usize = 20;
+
+    // max direct subdomains of a domain name
+    const MAX_SUBDOMAINS: usize = 50;
+
+    // lease-to-own plan, the buyer resolves the name while the seller keeps
+    // the title until the last installment
+    #[derive(Debug, scale::Decode, scale::Encode, Eq, PartialEq)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct Lease {
+        seller: AccountId,
+        buyer: AccountId,
+        deposit: u128,
+        installment: u128,
+        installments_left: u32,
+        interval: Timestamp,
+        // None until the buyer pays the deposit
+        next_due: Option<Timestamp>,
+        // payments held in escrow until the plan completes
+        paid: u128,
+    }
+
+    // rental lending resolution rights while the owner keeps the title
+    #[derive(Debug, scale::Decode, scale::Encode, Eq, PartialEq)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct Rental {
+        price: u128,
+        duration: Timestamp,
+        renter: Option<AccountId>,
+        end_time: Timestamp,
+    }
+
+    impl Rental {
+        fn active_renter(&self, now: Timestamp) -> Option<AccountId> {
+            self.renter.filter(|_| now < self.end_time)
+        }
+    }
+
+    // a completed sale of a domain name
+    #[derive(Debug, Clone, scale::Decode, scale::Encode, Eq, PartialEq)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct SaleRecord {
+        buyer: AccountId,
+        seller: AccountId,
+        price: u128,
+        currency: Currency,
+        block: BlockNumber,
+    }
+
+    // sales kept per domain name, older ones are dropped
+    const MAX_SALE_HISTORY: usize = 20;
+
+    const YEAR: Timestamp = 365 * 24 * 60 * 60 * 1000;
+
+    // default length of the grace and redemption periods after expiry
+    const DEFAULT_GRACE_PERIOD: Timestamp = 30 * 24 * 60 * 60 * 1000;
+    const DEFAULT_REDEMPTION_PERIOD: Timestamp = 30 * 24 * 60 * 60 * 1000;
+
+    // a registration commitment can be revealed between these ages
+    const MIN_COMMITMENT_AGE: Timestamp = 60 * 1000;
+    const MAX_COMMITMENT_AGE: Timestamp = 24 * 60 * 60 * 1000;
+
+    // most length tiers in the registration price schedule
+    const MAX_PRICE_TIERS: usize = 16;
+
+    // longest period a name can be registered or renewed for at once
+    const MAX_REGISTRATION_DURATION: Timestamp = 10 * YEAR;
+
+    // fees are expressed in basis points of the sale price
+    const BPS_DENOMINATOR: u128 = 10_000;
+
+    // token registration rates are token units per native unit, scaled by this
+    const RATE_DENOMINATOR: u128 = 1_000_000_000_000;
+
+    // highest royalty a registrant may set (10%)
+    const MAX_ROYALTY_BPS: u16 = 1_000;
+
+    // RFC 1035 limits on a name and each of its labels
+    const MAX_NAME_LENGTH: usize = 253;
+    const MAX_LABEL_LENGTH: usize = 63;
+
+    // RFC 3492 Punycode parameters
+    const PUNYCODE_BASE: u32 = 36;
+    const PUNYCODE_TMIN: u32 = 1;
+    const PUNYCODE_TMAX: u32 = 26;
+    const PUNYCODE_SKEW: u32 = 38;
+    const PUNYCODE_DAMP: u32 = 700;
+    const PUNYCODE_INITIAL_BIAS: u32 = 72;
+    const PUNYCODE_INITIAL_N: u32 = 128;
+
+    // prefix of a Punycode encoded label
+    const ACE_PREFIX: &str = "xn--";
+
     // define zero address function
     fn zero_address() -> AccountId {
         [0u8; 32].into()
     }
 
+    // map a name into its Punycode (xn--) A-label form, then check it against
+    // RFC 1035 label rules. This is the part of UTS-46 processing a contract
+    // can afford: ideographic dots separate labels, labels are lowercased and
+    // non-ASCII labels may only hold letters, digits and hyphens. Names are
+    // expected in NFC, clients normalize them before submitting.
+    fn normalize_name(name: &str) -> Result<String, DNSError> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(DNSError::EmptyName);
+        }
+        let mut ascii = String::with_capacity(name.len());
+        for (index, label) in name
+            .split(['.', '\u{3002}', '\u{ff0e}', '\u{ff61}'])
+            .enumerate()
+        {
+            if index > 0 {
+                ascii.push('.');
+            }
+            ascii.push_str(&to_ascii_label(label)?);
+        }
+        let name = ascii;
+        if name.is_empty() {
+            return Err(DNSError::EmptyName);
+        }
+        if name.len() > MAX_NAME_LENGTH {
+            return Err(DNSError::NameTooLong);
+        }
+
+        for label in name.split('.') {
+            if label.is_empty() {
+                return Err(DNSError::EmptyLabel);
+            }
+            if label.len() > MAX_LABEL_LENGTH {
+                return Err(DNSError::LabelTooLong);
+            }
+            if !label
+                .bytes()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == b'-')
+            {
+                return Err(DNSError::InvalidCharacter);
+            }
+            if label.starts_with('-') || label.ends_with('-') {
+                return Err(DNSError::InvalidHyphen);
+            }
+        }
+        Ok(name)
+    }
+
+    // lowercase a label and Punycode encode it if it isn't ASCII. A label
+    // already encoded must decode to the form it would be encoded from.
+    fn to_ascii_label(label: &str) -> Result<String, DNSError> {
+        // only ASCII is case folded; anything UTS-46 would map is rejected below
+        let label = label.to_ascii_lowercase();
+        if label.is_ascii() {
+            if let Some(encoded) = label.strip_prefix(ACE_PREFIX) {
+                let decoded = punycode_decode(encoded).ok_or(DNSError::InvalidIdn)?;
+                let unicode: String = decoded.iter().collect();
+                if unicode.is_ascii() || to_ascii_label(&unicode)? != label {
+                    return Err(DNSError::InvalidIdn);
+                }
+            }
+            return Ok(label);
+        }
+
+        let chars: Vec<char> = label.chars().collect();
+        let mut last_class = 0;
+        for (index, c) in chars.iter().enumerate() {
+            if c.is_ascii() {
+                if !(c.is_ascii_lowercase() || c.is_ascii_digit() || *c == '-') {
+                    return Err(DNSError::InvalidIdn);
+                }
+                last_class = 0;
+                continue;
+            }
+            if !is_valid_idn_char(*c) {
+                return Err(DNSError::InvalidIdn);
+            }
+            // a label can't start with a mark and marks must be in canonical order
+            let class = combining_class(*c);
+            if class != 0 && (index == 0 || class < last_class) {
+                return Err(DNSError::InvalidIdn);
+            }
+            last_class = class;
+        }
+        let encoded = punycode_encode(&chars).ok_or(DNSError::InvalidIdn)?;
+        Ok(format!("{}{}", ACE_PREFIX, encoded))
+    }
+
+    fn is_valid_idn_char(c: char) -> bool {
+        let c = c as u32;
+        VALID_RANGES
+            .binary_search_by(|&(low, high)| {
+                if high < c {
+                    core::cmp::Ordering::Less
+                } else if low > c {
+                    core::cmp::Ordering::Greater
+                } else {
+                    core::cmp::Ordering::Equal
+                }
+            })
+            .is_ok()
+    }
+
+    fn combining_class(c: char) -> u8 {
+        let c = c as u32;
+        COMBINING_CLASSES
+            .binary_search_by(|&(low, high, _)| {
+                if high < c {
+                    core::cmp::Ordering::Less
+                } else if low > c {
+                    core::cmp::Ordering::Greater
+                } else {
+                    core::cmp::Ordering::Equal
+                }
+            })
+            .map_or(0, |index| COMBINING_CLASSES[index].2)
+    }
+
+    // unicode display form of a normalized name
+    fn display_name(name: &str) -> String {
+        let labels: Vec<String> = name
+            .split('.')
+            .map(|label| {
+                label
+                    .strip_prefix(ACE_PREFIX)
+                    .and_then(punycode_decode)
+                    .map_or_else(|| label.into(), |decoded| decoded.iter().collect())
+            })
+            .collect();
+        labels.join(".")
+    }
+
+    fn punycode_threshold(k: u32, bias: u32) -> u32 {
+        if k <= bias {
+            PUNYCODE_TMIN
+        } else if k >= bias + PUNYCODE_TMAX {
+            PUNYCODE_TMAX
+        } else {
+            k - bias
+        }
+    }
+
+    fn punycode_adapt(delta: u32, num_points: u32, first_time: bool) -> u32 {
+        let mut delta = if first_time {
+            delta / PUNYCODE_DAMP
+        } else {
+            delta / 2
+        };
+        delta += delta / num_points;
+        let mut k = 0;
+        while delta > ((PUNYCODE_BASE - PUNYCODE_TMIN) * PUNYCODE_TMAX) / 2 {
+            delta /= PUNYCODE_BASE - PUNYCODE_TMIN;
+            k += PUNYCODE_BASE;
+        }
+        k + (PUNYCODE_BASE - PUNYCODE_TMIN + 1) * delta / (delta + PUNYCODE_SKEW)
+    }
+
+    fn punycode_digit(digit: u32) -> char {
+        if digit < 26 {
+            (b'a' + digit as u8) as char
+        } else {
+            (b'0' + (digit - 26) as u8) as char
+        }
+    }
+
+    // RFC 3492 encoding of a label, without the ACE prefix
+    fn punycode_encode(input: &[char]) -> Option<String> {
+        let mut output: String = input.iter().filter(|c| c.is_ascii()).collect();
+        let basic = output.len() as u32;
+        if basic > 0 {
+            output.push('-');
+        }
+
+        let mut n = PUNYCODE_INITIAL_N;
+        let mut delta: u32 = 0;
+        let mut bias = PUNYCODE_INITIAL_BIAS;
+        let mut handled = basic;
+        while (handled as usize) < input.len() {
+            let m = input.iter().map(|&c| c as u32).filter(|&c| c >= n).min()?;
+            delta = delta.checked_add((m - n).checked_mul(handled + 1)?)?;
+            n = m;
+            for &c in input {
+                let c = c as u32;
+                if c < n {
+                    delta = delta.checked_add(1)?;
+                }
+                if c == n {
+                    let mut q = delta;
+                    let mut k = PUNYCODE_BASE;
+                    loop {
+                        let t = punycode_threshold(k, bias);
+                        if q < t {
+                            break;
+                        }
+                        output.push(punycode_digit(t + (q - t) % (PUNYCODE_BASE - t)));
+                        q = (q - t) / (PUNYCODE_BASE - t);
+                        k += PUNYCODE_BASE;
+                    }
+                    output.push(punycode_digit(q));
+                    bias = punycode_adapt(delta, handled + 1, handled == basic);
+                    delta = 0;
+                    handled += 1;
+                }
+            }
+            delta = delta.checked_add(1)?;
+            n = n.checked_add(1)?;
+        }
+        Some(output)
+    }
+
+    // RFC 3492 decoding of a label given without the ACE prefix
+    fn punycode_decode(input: &str) -> Option<Vec<char>> {
+        let (basic, encoded) = match input.rfind('-') {
+            Some(index) => (&input[..index], &input[index + 1..]),
+            None => ("", input),
+        };
+        if !basic.is_ascii() {
+            return None;
+        }
+
+        let mut output: Vec<char> = basic.chars().collect();
+        let mut n = PUNYCODE_INITIAL_N;
+        let mut i: u32 = 0;
+        let mut bias = PUNYCODE_INITIAL_BIAS;
+        let mut digits = encoded.bytes().peekable();
+        while digits.peek().is_some() {
+            let old_i = i;
+            let mut weight: u32 = 1;
+            let mut k = PUNYCODE_BASE;
+            loop {
+                let digit = match digits.next()? {
+                    c @ b'a'..=b'z' => (c - b'a') as u32,
+                    c @ b'0'..=b'9' => (c - b'0') as u32 + 26,
+                    _ => return None,
+                };
+                i = i.checked_add(digit.checked_mul(weight)?)?;
+                let t = punycode_threshold(k, bias);
+                if digit < t {
+                    break;
+                }
+                weight = weight.checked_mul(PUNYCODE_BASE - t)?;
+                k += PUNYCODE_BASE;
+            }
+            let length = output.len() as u32 + 1;
+            bias = punycode_adapt(i - old_i, length, old_i == 0);
+            n = n.checked_add(i / length)?;
+            i %= length;
+            output.insert(i as usize, char::from_u32(n)?);
+            i += 1;
+        }
+        Some(output)
+    }
+
     #[ink(storage)]
     pub struct DnsContract {
         owner: AccountId,
@@ -60,6 +693,38 @@ mod dns_contract {
         claimed: Mapping<DomainNameId, bool>,
         no_of_claimed_names: i32,
         domain_name_id: i32,
+        auctions: Mapping<DomainNameId, Auction>,
+        pending_returns: Mapping<AccountId, u128>,
+        sealed_auctions: Mapping<String, SealedAuction>,
+        sealed_bids: Mapping<(String, AccountId), SealedBid>,
+        standing_bids: Mapping<DomainNameId, Vec<StandingBid>>,
+        fee_bps: u16,
+        treasury: u128,
+        bundles: Mapping<BundleId, Bundle>,
+        bundle_id: BundleId,
+        negotiations: Mapping<(DomainNameId, AccountId), Negotiation>,
+        sale_history: Mapping<DomainNameId, Vec<SaleRecord>>,
+        accepted_tokens: Mapping<AccountId, ()>,
+        token_treasury: Mapping<AccountId, u128>,
+        leases: Mapping<DomainNameId, Lease>,
+        name_to_id: Mapping<String, DomainNameId>,
+        rentals: Mapping<DomainNameId, Rental>,
+        // resolution is kept per controller so it reverts with control
+        resolved_address: Mapping<(DomainNameId, AccountId), AccountId>,
+        records: Mapping<RecordKey, String>,
+        // yearly registration price by name length: entry i applies to names
+        // of i + 1 characters and the last entry to all longer names
+        price_schedule: Vec<u128>,
+        grace_period: Timestamp,
+        redemption_period: Timestamp,
+        redemption_fee: u128,
+        commitments: Mapping<Hash, Timestamp>,
+        reserved_names: Mapping<String, ()>,
+        tlds: Mapping<String, TldPolicy>,
+        tld_list: Vec<String>,
+        subdomains: Mapping<DomainNameId, Vec<DomainNameId>>,
+        registrars: Mapping<DomainNameId, SubdomainRegistrar>,
+        token_rates: Mapping<AccountId, u128>,
     }
 
     /// Errors that can occur upon calling this contract.
@@ -72,6 +737,83 @@ mod dns_contract {
         SameOwner,
         NameAlreadyClaimed,
         DomainAlreadyOwned,
+        DomainNotFound,
+        NotForSale,
+        IncorrectPayment,
+        TransferFailed,
+        NotDesignatedBuyer,
+        PrivateOfferExists,
+        NoPrivateOffer,
+        DomainInAuction,
+        NoAuction,
+        InvalidEndTime,
+        AuctionEnded,
+        AuctionNotEnded,
+        BidTooLow,
+        AuctionHasBids,
+        NothingToWithdraw,
+        NotInCommitPhase,
+        NotInRevealPhase,
+        BidAlreadyCommitted,
+        NoSealedBid,
+        InvalidReveal,
+        InvalidDutchListing,
+        NoDutchListing,
+        BidExpired,
+        NoStandingBid,
+        BidBookFull,
+        InvalidFee,
+        InsufficientTreasury,
+        NotRegistrant,
+        InvalidRoyalty,
+        OfferExpired,
+        InvalidExpiry,
+        InvalidBundle,
+        BundleNotFound,
+        BundleMemberChanged,
+        NoNegotiation,
+        NoCounterOffer,
+        TokenNotAccepted,
+        TokenTransferFailed,
+        DomainLeased,
+        NoLease,
+        InvalidLeaseTerms,
+        LeaseAlreadyStarted,
+        LeaseNotStarted,
+        LeaseDefaulted,
+        PaymentNotOverdue,
+        DomainRented,
+        NoRental,
+        InvalidDuration,
+        NotController,
+        DomainExpired,
+        InvalidPriceSchedule,
+        CommitmentExists,
+        NoCommitment,
+        CommitmentTooNew,
+        CommitmentExpired,
+        EmptyName,
+        NameTooLong,
+        EmptyLabel,
+        LabelTooLong,
+        InvalidCharacter,
+        InvalidHyphen,
+        InvalidIdn,
+        NameReserved,
+        NameNotReserved,
+        TldExists,
+        TldNotFound,
+        TldClosed,
+        NotUnderTld,
+        LabelLengthNotAllowed,
+        InvalidTldPolicy,
+        IsSubdomain,
+        TooManySubdomains,
+        RegistrarClosed,
+        InvalidRegistrar,
+        PriceMismatch,
+        NoTokenRate,
+        InvalidOfferState,
     }
 
     // events message
@@ -87,60 +829,491 @@ mod dns_contract {
         address: AccountId,
     }
 
-    impl DnsContract {
-        #[ink(constructor)]
-        pub fn new() -> Self {
-            Self {
-                owner: Self::env().caller(),
-                owner_name_count: Mapping::default(),
-                domain_name: Mapping::default(),
-                name_to_owner: Mapping::default(),
-                claimed: Mapping::default(),
-                no_of_claimed_names: Default::default(),
-                domain_name_id: 1,
-            }
-        }
-
-        #[ink(message)]
-        pub fn create_new_dns(
-            &mut self,
-            name: String,
-            offer_state: State,
-            offer_price: u128,
-        ) -> Result<(), DNSError> {
-            let name_id = self.next_domain_name_id();
-            let claimed = self.claimed.get(name_id).unwrap_or_default();
-            let caller = self.env().caller();
-
-            if self.name_to_owner.contains(&name) {
-                return Err(DNSError::DomainAlreadyOwned);
-            }
+    #[ink(event)]
+    pub struct NameRenewed {
+        #[ink(topic)]
+        name_id: DomainNameId,
+        expires_at: Timestamp,
+    }
 
-            // insert name to owner
-            self.name_to_owner.insert(&name, &caller);
+    #[ink(event)]
+    pub struct PriceScheduleChanged {
+        prices: Vec<u128>,
+    }
 
-            // check name mustn't be already claimed
-            if claimed {
-                return Err(DNSError::NameAlreadyClaimed);
-            }
+    #[ink(event)]
+    pub struct ExpiryPeriodsChanged {
+        grace_period: Timestamp,
+        redemption_period: Timestamp,
+        redemption_fee: u128,
+    }
 
-            let domain_name = DomainName {
-                name,
-                offer_state,
-                offer_price,
-                default_address: caller,
-            };
+    #[ink(event)]
+    pub struct ReservationsChanged {
+        names: Vec<String>,
+        reserved: bool,
+    }
 
-            self.domain_name.insert(name_id, &domain_name);
-            self.claimed.insert(name_id, &true);
-            self.no_of_claimed_names += 1;
+    #[ink(event)]
+    pub struct SubdomainCreated {
+        #[ink(topic)]
+        parent_id: DomainNameId,
+        name_id: DomainNameId,
+        owner: AccountId,
+    }
 
-            let name_count = self.owner_name_count.get(caller).unwrap_or_default();
-            self.owner_name_count.insert(caller, &(name_count + 1));
+    #[ink(event)]
+    pub struct SubdomainRegistrarSet {
+        #[ink(topic)]
+        name_id: DomainNameId,
+        registrar: Option<SubdomainRegistrar>,
+    }
 
-            self.env().emit_event(NewNameClaimed { address: caller });
-            Ok(())
-        }
+    #[ink(event)]
+    pub struct TldPolicySet {
+        tld: String,
+        policy: TldPolicy,
+    }
+
+    #[ink(event)]
+    pub struct DomainSold {
+        #[ink(topic)]
+        name_id: DomainNameId,
+        #[ink(topic)]
+        seller: AccountId,
+        #[ink(topic)]
+        buyer: AccountId,
+        price: u128,
+    }
+
+    #[ink(event)]
+    pub struct FeeChanged {
+        old_fee_bps: u16,
+        new_fee_bps: u16,
+    }
+
+    #[ink(event)]
+    pub struct TreasuryWithdrawn {
+        #[ink(topic)]
+        to: AccountId,
+        amount: u128,
+    }
+
+    #[ink(event)]
+    pub struct TokenAcceptanceChanged {
+        #[ink(topic)]
+        token: AccountId,
+        accepted: bool,
+    }
+
+    #[ink(event)]
+    pub struct TokenRateChanged {
+        #[ink(topic)]
+        token: AccountId,
+        rate: Option<u128>,
+    }
+
+    #[ink(event)]
+    pub struct TokenTreasuryWithdrawn {
+        #[ink(topic)]
+        token: AccountId,
+        #[ink(topic)]
+        to: AccountId,
+        amount: u128,
+    }
+
+    #[ink(event)]
+    pub struct RoyaltyChanged {
+        #[ink(topic)]
+        name_id: DomainNameId,
+        royalty_bps: u16,
+    }
+
+    #[ink(event)]
+    pub struct RoyaltyPaid {
+        #[ink(topic)]
+        name_id: DomainNameId,
+        #[ink(topic)]
+        registrant: AccountId,
+        amount: u128,
+    }
+
+    #[ink(event)]
+    pub struct DomainListed {
+        #[ink(topic)]
+        name_id: DomainNameId,
+        price: u128,
+        currency: Currency,
+        expires_at: Option<Timestamp>,
+    }
+
+    #[ink(event)]
+    pub struct DomainDelisted {
+        #[ink(topic)]
+        name_id: DomainNameId,
+    }
+
+    #[ink(event)]
+    pub struct BundleCreated {
+        #[ink(topic)]
+        bundle_id: BundleId,
+        #[ink(topic)]
+        seller: AccountId,
+        name_ids: Vec<DomainNameId>,
+        price: u128,
+    }
+
+    #[ink(event)]
+    pub struct BundleCancelled {
+        #[ink(topic)]
+        bundle_id: BundleId,
+    }
+
+    #[ink(event)]
+    pub struct BundleSold {
+        #[ink(topic)]
+        bundle_id: BundleId,
+        #[ink(topic)]
+        buyer: AccountId,
+        price: u128,
+    }
+
+    #[ink(event)]
+    pub struct PriceProposed {
+        #[ink(topic)]
+        name_id: DomainNameId,
+        #[ink(topic)]
+        buyer: AccountId,
+        price: u128,
+    }
+
+    #[ink(event)]
+    pub struct CounterOffered {
+        #[ink(topic)]
+        name_id: DomainNameId,
+        #[ink(topic)]
+        buyer: AccountId,
+        price: u128,
+    }
+
+    #[ink(event)]
+    pub struct NegotiationClosed {
+        #[ink(topic)]
+        name_id: DomainNameId,
+        #[ink(topic)]
+        buyer: AccountId,
+        // agreed price, None when rejected or withdrawn
+        price: Option<u128>,
+    }
+
+    #[ink(event)]
+    pub struct PrivateOfferSet {
+        #[ink(topic)]
+        name_id: DomainNameId,
+        #[ink(topic)]
+        buyer: AccountId,
+        price: u128,
+        currency: Currency,
+        expires_at: Option<Timestamp>,
+    }
+
+    #[ink(event)]
+    pub struct PrivateOfferRevoked {
+        #[ink(topic)]
+        name_id: DomainNameId,
+    }
+
+    #[ink(event)]
+    pub struct DutchListingCreated {
+        #[ink(topic)]
+        name_id: DomainNameId,
+        start_price: u128,
+        floor_price: u128,
+        end_block: BlockNumber,
+    }
+
+    #[ink(event)]
+    pub struct DutchListingCancelled {
+        #[ink(topic)]
+        name_id: DomainNameId,
+    }
+
+    #[ink(event)]
+    pub struct StandingBidPlaced {
+        #[ink(topic)]
+        name_id: DomainNameId,
+        #[ink(topic)]
+        bidder: AccountId,
+        amount: u128,
+        expires_at: Timestamp,
+    }
+
+    #[ink(event)]
+    pub struct StandingBidCancelled {
+        #[ink(topic)]
+        name_id: DomainNameId,
+        #[ink(topic)]
+        bidder: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct LeaseOffered {
+        #[ink(topic)]
+        name_id: DomainNameId,
+        #[ink(topic)]
+        buyer: AccountId,
+        deposit: u128,
+        installment: u128,
+        installments: u32,
+        interval: Timestamp,
+    }
+
+    #[ink(event)]
+    pub struct LeasePayment {
+        #[ink(topic)]
+        name_id: DomainNameId,
+        #[ink(topic)]
+        buyer: AccountId,
+        amount: u128,
+        installments_left: u32,
+    }
+
+    #[ink(event)]
+    pub struct LeaseEnded {
+        #[ink(topic)]
+        name_id: DomainNameId,
+        #[ink(topic)]
+        buyer: AccountId,
+        // true when the buyer got the title, false when cancelled or defaulted
+        completed: bool,
+    }
+
+    #[ink(event)]
+    pub struct RentalOffered {
+        #[ink(topic)]
+        name_id: DomainNameId,
+        price: u128,
+        duration: Timestamp,
+    }
+
+    #[ink(event)]
+    pub struct DomainRented {
+        #[ink(topic)]
+        name_id: DomainNameId,
+        #[ink(topic)]
+        renter: AccountId,
+        price: u128,
+        end_time: Timestamp,
+    }
+
+    #[ink(event)]
+    pub struct ResolvedAddressChanged {
+        #[ink(topic)]
+        name_id: DomainNameId,
+        #[ink(topic)]
+        controller: AccountId,
+        address: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct RecordChanged {
+        #[ink(topic)]
+        name_id: DomainNameId,
+        #[ink(topic)]
+        controller: AccountId,
+        key: String,
+        value: String,
+    }
+
+    #[ink(event)]
+    pub struct AuctionStarted {
+        #[ink(topic)]
+        name_id: DomainNameId,
+        #[ink(topic)]
+        seller: AccountId,
+        reserve_price: u128,
+        end_time: Timestamp,
+    }
+
+    #[ink(event)]
+    pub struct BidPlaced {
+        #[ink(topic)]
+        name_id: DomainNameId,
+        #[ink(topic)]
+        bidder: AccountId,
+        amount: u128,
+        end_time: Timestamp,
+    }
+
+    #[ink(event)]
+    pub struct AuctionSettled {
+        #[ink(topic)]
+        name_id: DomainNameId,
+        #[ink(topic)]
+        winner: Option<AccountId>,
+        price: u128,
+    }
+
+    #[ink(event)]
+    pub struct Withdrawn {
+        #[ink(topic)]
+        account: AccountId,
+        amount: u128,
+    }
+
+    #[ink(event)]
+    pub struct SealedBidCommitted {
+        name: String,
+        #[ink(topic)]
+        bidder: AccountId,
+        deposit: u128,
+    }
+
+    #[ink(event)]
+    pub struct SealedBidRevealed {
+        name: String,
+        #[ink(topic)]
+        bidder: AccountId,
+        bid: u128,
+    }
+
+    #[ink(event)]
+    pub struct SealedAuctionFinalized {
+        name: String,
+        #[ink(topic)]
+        winner: Option<AccountId>,
+        price: u128,
+    }
+
+    impl DnsContract {
+        #[ink(constructor)]
+        pub fn new() -> Self {
+            Self {
+                owner: Self::env().caller(),
+                owner_name_count: Mapping::default(),
+                domain_name: Mapping::default(),
+                name_to_owner: Mapping::default(),
+                claimed: Mapping::default(),
+                no_of_claimed_names: Default::default(),
+                domain_name_id: 1,
+                auctions: Mapping::default(),
+                pending_returns: Mapping::default(),
+                sealed_auctions: Mapping::default(),
+                sealed_bids: Mapping::default(),
+                standing_bids: Mapping::default(),
+                fee_bps: 0,
+                treasury: 0,
+                bundles: Mapping::default(),
+                bundle_id: 1,
+                negotiations: Mapping::default(),
+                sale_history: Mapping::default(),
+                accepted_tokens: Mapping::default(),
+                token_treasury: Mapping::default(),
+                leases: Mapping::default(),
+                name_to_id: Mapping::default(),
+                rentals: Mapping::default(),
+                resolved_address: Mapping::default(),
+                records: Mapping::default(),
+                price_schedule: Vec::new(),
+                grace_period: DEFAULT_GRACE_PERIOD,
+                redemption_period: DEFAULT_REDEMPTION_PERIOD,
+                redemption_fee: 0,
+                commitments: Mapping::default(),
+                reserved_names: Mapping::default(),
+                tlds: Mapping::default(),
+                tld_list: Vec::new(),
+                subdomains: Mapping::default(),
+                registrars: Mapping::default(),
+                token_rates: Mapping::default(),
+            }
+        }
+
+        // commit to a registration without revealing the name, see make_commitment
+        #[ink(message)]
+        pub fn commit(&mut self, commitment: Hash) -> Result<(), DNSError> {
+            let now = self.env().block_timestamp();
+            if let Some(committed_at) = self.commitments.get(commitment) {
+                if now <= committed_at + MAX_COMMITMENT_AGE {
+                    return Err(DNSError::CommitmentExists);
+                }
+            }
+            self.commitments.insert(commitment, &now);
+            Ok(())
+        }
+
+        // register a name for duration, paying the registration price. The
+        // caller must have committed to the name earlier so it can't be
+        // front-run, the commitment must be older than the minimum age and
+        // not older than the maximum.
+        #[ink(message, payable)]
+        pub fn create_new_dns(
+            &mut self,
+            name: String,
+            secret: [u8; 32],
+            offer_state: State,
+            offer_price: u128,
+            duration: Timestamp,
+            currency: Currency,
+        ) -> Result<(), DNSError> {
+            self.take_commitment(&name, secret)?;
+            self.register_paid(name, offer_state, offer_price, duration, currency)
+        }
+
+        // commitment hash for registering name to owner
+        #[ink(message)]
+        pub fn make_commitment(&self, name: String, owner: AccountId, secret: [u8; 32]) -> Hash {
+            Hash::from(
+                self.env()
+                    .hash_encoded::<Blake2x256, _>(&(name, owner, secret)),
+            )
+        }
+
+        // extend a registration by duration. Anyone may pay for an active
+        // name, after expiry only the previous owner may renew and during
+        // redemption the redemption fee is added to the price.
+        #[ink(message, payable)]
+        pub fn renew(
+            &mut self,
+            name_id: DomainNameId,
+            duration: Timestamp,
+            currency: Currency,
+        ) -> Result<(), DNSError> {
+            let mut domain = self
+                .domain_name
+                .get(name_id)
+                .ok_or(DNSError::DomainNotFound)?;
+            // subdomains expire with their parent or renew through its registrar
+            if domain.parent.is_some() {
+                return Err(DNSError::IsSubdomain);
+            }
+            let penalty = match self.phase_of(&domain) {
+                NamePhase::Active => 0,
+                NamePhase::GracePeriod => {
+                    self.ensure_previous_owner(&domain)?;
+                    0
+                }
+                NamePhase::Redemption => {
+                    self.ensure_previous_owner(&domain)?;
+                    self.redemption_fee
+                }
+                NamePhase::Available => return Err(DNSError::DomainExpired),
+            };
+            self.collect_registration_price(&domain.name, duration, penalty, currency)?;
+
+            let previous_expiry = domain.expires_at;
+            domain.expires_at += duration;
+            if domain.is_expired(self.env().block_timestamp()) {
+                return Err(DNSError::InvalidDuration);
+            }
+            self.domain_name.insert(name_id, &domain);
+            self.extend_subdomains(name_id, previous_expiry, domain.expires_at);
+
+            self.env().emit_event(NameRenewed {
+                name_id,
+                expires_at: domain.expires_at,
+            });
+            Ok(())
+        }
 
         #[ink(message)]
         pub fn set_new_owner(
@@ -151,87 +1324,3858 @@ mod dns_contract {
             let name = self.domain_name.get(name_id);
             let caller = self.env().caller();
 
-            match name {
-                Some(value) => {
-                    if value.default_address != caller {
-                        return Err(DNSError::NotAOwner);
-                    }
-                    // make sure domain_name.owner != new_owner
-                    if value.default_address == new_owner {
-                        return Err(DNSError::SameOwner);
-                    }
-
-                    // owner transfer so owner_name_count descrease
-                    let name_count = self.owner_name_count.get(caller).unwrap_or_default();
-                    self.owner_name_count.insert(caller, &(name_count - 1));
-
-                    // remove domain name claimed of this id
-                    let name_claimed = self.claimed.get(name_id).unwrap_or_default();
-                    self.claimed.insert(name_id, &!name_claimed);
-
-                    let domain_name = DomainName {
-                        name: value.name,
-                        offer_state: value.offer_state,
-                        offer_price: value.offer_price,
-                        default_address: new_owner,
-                    };
-
-                    self.domain_name.insert(name_id, &domain_name);
+            if let Some(value) = name {
+                if value.default_address != caller {
+                    return Err(DNSError::NotAOwner);
+                }
+                if value.is_expired(self.env().block_timestamp()) {
+                    return Err(DNSError::DomainExpired);
                 }
-                None => (),
+                self.ensure_transferable(name_id)?;
+                // make sure domain_name.owner != new_owner
+                if value.default_address == new_owner {
+                    return Err(DNSError::SameOwner);
+                }
+
+                // moves the owner counts and takes the name off the market
+                self.transfer_domain(name_id, value, new_owner);
             }
 
             self.env().emit_event(SetNewOwner { address: new_owner });
             Ok(())
         }
 
-        #[ink(message)]
-        pub fn get_owner_domain_name(&self) -> Vec<DomainName> {
-            let mut domain_name: Vec<DomainName> = Vec::new();
+        // buy a domain name listed as public or private offering at its offer
+        // price, which may not exceed max_price
+        #[ink(message, payable)]
+        pub fn buy_domain(
+            &mut self,
+            name_id: DomainNameId,
+            max_price: u128,
+        ) -> Result<(), DNSError> {
+            let domain = self.live_domain(name_id)?;
             let caller = self.env().caller();
+            let seller = domain.default_address;
 
-            for _item in 0..self.domain_name_id {
-                let name = self.domain_name.get(_item);
-                match name {
-                    Some(value) => {
-                        if value.default_address == caller {
-                            domain_name.push(value);
-                        }
-                    }
-                    None => (),
+            if domain.offer_lapsed(self.env().block_timestamp()) {
+                return Err(DNSError::OfferExpired);
+            }
+            if let State::PrivateOffering(buyer) = domain.offer_state {
+                if buyer != caller {
+                    return Err(DNSError::NotDesignatedBuyer);
                 }
             }
+            if seller == caller {
+                return Err(DNSError::SameOwner);
+            }
+            self.ensure_transferable(name_id)?;
 
-            domain_name
+            let price = self.current_price(&domain)?;
+            // the seller may have relisted at a higher price, token buyers
+            // are only bound by their allowance otherwise
+            if price > max_price {
+                return Err(DNSError::PriceMismatch);
+            }
+            let paid = self.env().transferred_value();
+            let currency = domain.offer_currency;
+            if let Currency::Psp22(_) = currency {
+                // token listings are paid through the token allowance
+                if paid != 0 {
+                    return Err(DNSError::IncorrectPayment);
+                }
+            } else if let State::DutchOffering(_) = domain.offer_state {
+                // the price may drop between signing and execution, refund the difference
+                if paid < price {
+                    return Err(DNSError::IncorrectPayment);
+                }
+                self.pay(caller, paid - price)?;
+            } else if paid != price {
+                return Err(DNSError::IncorrectPayment);
+            }
+
+            self.settle_sale(name_id, domain, caller, price, currency)
         }
 
-        // get a owner of contract
+        // list a domain name publicly at a fixed price in native balance or
+        // an accepted PSP22 token
         #[ink(message)]
-        pub fn get_owner(&self) -> AccountId {
-            self.owner.clone()
+        pub fn list_domain(
+            &mut self,
+            name_id: DomainNameId,
+            price: u128,
+            currency: Currency,
+            expires_at: Option<Timestamp>,
+        ) -> Result<(), DNSError> {
+            let mut domain = self.owned_domain(name_id)?;
+            self.ensure_transferable(name_id)?;
+            self.ensure_valid_expiry(expires_at)?;
+            self.ensure_accepted(currency)?;
+
+            domain.offer_state = State::PublicOffering;
+            domain.offer_price = price;
+            domain.offer_currency = currency;
+            domain.offer_expires_at = expires_at;
+            self.domain_name.insert(name_id, &domain);
+
+            self.env().emit_event(DomainListed {
+                name_id,
+                price,
+                currency,
+                expires_at,
+            });
+            Ok(())
         }
 
+        // take a domain name off the market, whatever the kind of listing
         #[ink(message)]
-        pub fn get_no_of_name_claimed(&self) -> i32 {
-            self.no_of_claimed_names.clone()
+        pub fn delist_domain(&mut self, name_id: DomainNameId) -> Result<(), DNSError> {
+            let mut domain = self.owned_domain(name_id)?;
+            if domain.offer_state == State::NotOffering {
+                return Err(DNSError::NotForSale);
+            }
+
+            domain.clear_offer();
+            self.domain_name.insert(name_id, &domain);
+
+            self.env().emit_event(DomainDelisted { name_id });
+            Ok(())
         }
 
-        // get domain name count
+        // A-label and unicode display form of a domain name
         #[ink(message)]
-        pub fn get_owner_name_count(&self, account_id: AccountId) -> i32 {
-            self.owner_name_count.get(account_id).unwrap_or_default() as i32
+        pub fn get_name_forms(&self, name_id: DomainNameId) -> Option<(String, String)> {
+            let domain = self.domain_name.get(name_id)?;
+            let display = display_name(&domain.name);
+            Some((domain.name, display))
         }
 
         #[ink(message)]
-        pub fn is_claimed(&self, id: DomainNameId) -> bool {
-            self.claimed.get(id).unwrap_or_default()
+        pub fn get_domain(&self, name_id: DomainNameId) -> Option<DomainName> {
+            self.domain_name
+                .get(name_id)
+                .map(|domain| self.effective_domain(domain))
         }
 
-        #[inline]
-        fn next_domain_name_id(&mut self) -> DomainNameId {
-            let id = self.domain_name_id;
-            self.domain_name_id += 1;
+        // list several domain names of the caller for a single price
+        #[ink(message)]
+        pub fn create_bundle(
+            &mut self,
+            name_ids: Vec<DomainNameId>,
+            price: u128,
+        ) -> Result<BundleId, DNSError> {
+            if name_ids.is_empty() || name_ids.len() > MAX_BUNDLE_SIZE {
+                return Err(DNSError::InvalidBundle);
+            }
+            for (index, name_id) in name_ids.iter().enumerate() {
+                if name_ids[..index].contains(name_id) {
+                    return Err(DNSError::InvalidBundle);
+                }
+                self.owned_domain(*name_id)?;
+                self.ensure_transferable(*name_id)?;
+            }
+
+            let bundle_id = self.bundle_id;
+            self.bundle_id += 1;
+
+            let seller = self.env().caller();
+            self.bundles.insert(
+                bundle_id,
+                &Bundle {
+                    seller,
+                    name_ids: name_ids.clone(),
+                    price,
+                },
+            );
+
+            self.env().emit_event(BundleCreated {
+                bundle_id,
+                seller,
+                name_ids,
+                price,
+            });
+            Ok(bundle_id)
+        }
+
+        // withdraw a bundle listing, seller only
+        #[ink(message)]
+        pub fn cancel_bundle(&mut self, bundle_id: BundleId) -> Result<(), DNSError> {
+            let bundle = self
+                .bundles
+                .get(bundle_id)
+                .ok_or(DNSError::BundleNotFound)?;
+            if bundle.seller != self.env().caller() {
+                return Err(DNSError::NotAOwner);
+            }
+            self.bundles.remove(bundle_id);
+
+            self.env().emit_event(BundleCancelled { bundle_id });
+            Ok(())
+        }
+
+        // buy every domain name of a bundle at once. Fails entirely if any of
+        // them is no longer owned by the seller.
+        #[ink(message, payable)]
+        pub fn buy_bundle(&mut self, bundle_id: BundleId) -> Result<(), DNSError> {
+            let bundle = self
+                .bundles
+                .get(bundle_id)
+                .ok_or(DNSError::BundleNotFound)?;
+            let caller = self.env().caller();
+
+            if bundle.seller == caller {
+                return Err(DNSError::SameOwner);
+            }
+            if self.env().transferred_value() != bundle.price {
+                return Err(DNSError::IncorrectPayment);
+            }
+
+            let mut domains = Vec::with_capacity(bundle.name_ids.len());
+            for name_id in &bundle.name_ids {
+                let domain = self.live_domain(*name_id)?;
+                if domain.default_address != bundle.seller {
+                    return Err(DNSError::BundleMemberChanged);
+                }
+                self.ensure_transferable(*name_id)?;
+                domains.push(domain);
+            }
+            self.bundles.remove(bundle_id);
+
+            // split the price evenly, the last name takes the rounding remainder
+            let count = bundle.name_ids.len() as u128;
+            let share = bundle.price / count;
+            let remainder = bundle.price - share * count;
+            for (index, (name_id, domain)) in bundle.name_ids.iter().zip(domains).enumerate() {
+                let price = if index as u128 == count - 1 {
+                    share + remainder
+                } else {
+                    share
+                };
+                self.settle_sale(*name_id, domain, caller, price, Currency::Native)?;
+            }
+
+            self.env().emit_event(BundleSold {
+                bundle_id,
+                buyer: caller,
+                price: bundle.price,
+            });
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn get_bundle(&self, bundle_id: BundleId) -> Option<Bundle> {
+            self.bundles.get(bundle_id)
+        }
+
+        // price the domain name last sold for
+        #[ink(message)]
+        pub fn get_last_sale_price(&self, name_id: DomainNameId) -> Option<u128> {
+            self.sale_history
+                .get(name_id)
+                .and_then(|history| history.last().map(|sale| sale.price))
+        }
+
+        // recorded sales of a domain name, oldest first
+        #[ink(message)]
+        pub fn get_sale_history(
+            &self,
+            name_id: DomainNameId,
+            offset: u32,
+            limit: u32,
+        ) -> Vec<SaleRecord> {
+            self.sale_history
+                .get(name_id)
+                .unwrap_or_default()
+                .into_iter()
+                .skip(offset as usize)
+                .take(limit as usize)
+                .collect()
+        }
+
+        // current price of a listed domain name
+        #[ink(message)]
+        pub fn quote_price(&self, name_id: DomainNameId) -> Result<u128, DNSError> {
+            let domain = self.live_domain(name_id)?;
+            self.current_price(&domain)
+        }
+
+        // list a domain name at a price decaying to floor_price over duration blocks
+        #[ink(message)]
+        pub fn create_dutch_listing(
+            &mut self,
+            name_id: DomainNameId,
+            start_price: u128,
+            floor_price: u128,
+            duration: BlockNumber,
+            curve: PriceCurve,
+        ) -> Result<(), DNSError> {
+            let mut domain = self.owned_domain(name_id)?;
+            self.ensure_transferable(name_id)?;
+            if start_price < floor_price
+                || duration == 0
+                || curve == (PriceCurve::Exponential { half_life: 0 })
+            {
+                return Err(DNSError::InvalidDutchListing);
+            }
+
+            let start_block = self.env().block_number();
+            let end_block = start_block
+                .checked_add(duration)
+                .ok_or(DNSError::InvalidDutchListing)?;
+
+            domain.offer_state = State::DutchOffering(DutchListing {
+                start_price,
+                floor_price,
+                start_block,
+                end_block,
+                curve,
+            });
+            domain.offer_price = start_price;
+            domain.offer_currency = Currency::Native;
+            domain.offer_expires_at = None;
+            self.domain_name.insert(name_id, &domain);
+
+            self.env().emit_event(DutchListingCreated {
+                name_id,
+                start_price,
+                floor_price,
+                end_block,
+            });
+            Ok(())
+        }
+
+        // take a domain name off dutch listing
+        #[ink(message)]
+        pub fn cancel_dutch_listing(&mut self, name_id: DomainNameId) -> Result<(), DNSError> {
+            let mut domain = self.owned_domain(name_id)?;
+            if !matches!(domain.offer_state, State::DutchOffering(_)) {
+                return Err(DNSError::NoDutchListing);
+            }
+
+            domain.clear_offer();
+            self.domain_name.insert(name_id, &domain);
+
+            self.env().emit_event(DutchListingCancelled { name_id });
+            Ok(())
+        }
+
+        // offer a domain name privately to a single buyer
+        #[ink(message)]
+        pub fn create_private_offer(
+            &mut self,
+            name_id: DomainNameId,
+            buyer: AccountId,
+            price: u128,
+            currency: Currency,
+            expires_at: Option<Timestamp>,
+        ) -> Result<(), DNSError> {
+            let domain = self.owned_domain(name_id)?;
+            self.ensure_transferable(name_id)?;
+            let lapsed = domain.offer_lapsed(self.env().block_timestamp());
+            if matches!(domain.offer_state, State::PrivateOffering(_)) && !lapsed {
+                return Err(DNSError::PrivateOfferExists);
+            }
+            self.set_private_offer(name_id, domain, buyer, price, currency, expires_at)
+        }
+
+        // change the buyer or price of an existing private offer
+        #[ink(message)]
+        pub fn update_private_offer(
+            &mut self,
+            name_id: DomainNameId,
+            buyer: AccountId,
+            price: u128,
+            currency: Currency,
+            expires_at: Option<Timestamp>,
+        ) -> Result<(), DNSError> {
+            let domain = self.owned_domain(name_id)?;
+            if !matches!(domain.offer_state, State::PrivateOffering(_)) {
+                return Err(DNSError::NoPrivateOffer);
+            }
+            self.set_private_offer(name_id, domain, buyer, price, currency, expires_at)
+        }
+
+        // take a domain name off private offering
+        #[ink(message)]
+        pub fn revoke_private_offer(&mut self, name_id: DomainNameId) -> Result<(), DNSError> {
+            let mut domain = self.owned_domain(name_id)?;
+            if !matches!(domain.offer_state, State::PrivateOffering(_)) {
+                return Err(DNSError::NoPrivateOffer);
+            }
+
+            domain.clear_offer();
+            self.domain_name.insert(name_id, &domain);
+
+            self.env().emit_event(PrivateOfferRevoked { name_id });
+            Ok(())
+        }
+
+        // propose a price to the owner of a name, the transferred value is the
+        // proposed price and stays in escrow. A new proposal replaces the
+        // previous one and makes its escrow withdrawable.
+        #[ink(message, payable)]
+        pub fn propose_price(&mut self, name_id: DomainNameId) -> Result<(), DNSError> {
+            let domain = self.live_domain(name_id)?;
+            let caller = self.env().caller();
+            let price = self.env().transferred_value();
+
+            if domain.default_address == caller {
+                return Err(DNSError::SameOwner);
+            }
+            if price == 0 {
+                return Err(DNSError::BidTooLow);
+            }
+
+            if let Some(previous) = self.negotiations.get((name_id, caller)) {
+                self.credit(caller, previous.escrow);
+            }
+            self.negotiations.insert(
+                (name_id, caller),
+                &Negotiation {
+                    escrow: price,
+                    counter_price: None,
+                    countered_by: None,
+                },
+            );
+
+            self.env().emit_event(PriceProposed {
+                name_id,
+                buyer: caller,
+                price,
+            });
+            Ok(())
+        }
+
+        // answer a proposal with a different price, owner only
+        #[ink(message)]
+        pub fn counter_offer(
+            &mut self,
+            name_id: DomainNameId,
+            buyer: AccountId,
+            price: u128,
+        ) -> Result<(), DNSError> {
+            let domain = self.owned_domain(name_id)?;
+            let mut negotiation = self
+                .negotiations
+                .get((name_id, buyer))
+                .ok_or(DNSError::NoNegotiation)?;
+
+            negotiation.counter_price = Some(price);
+            negotiation.countered_by = Some(domain.default_address);
+            self.negotiations.insert((name_id, buyer), &negotiation);
+
+            self.env().emit_event(CounterOffered {
+                name_id,
+                buyer,
+                price,
+            });
+            Ok(())
+        }
+
+        // accept the buyer's proposed price, owner only. The price is the one
+        // the owner agrees to, the buyer may have replaced the proposal since.
+        #[ink(message)]
+        pub fn accept_proposal(
+            &mut self,
+            name_id: DomainNameId,
+            buyer: AccountId,
+            price: u128,
+        ) -> Result<(), DNSError> {
+            let domain = self.owned_domain(name_id)?;
+            self.ensure_transferable(name_id)?;
+            let negotiation = self
+                .negotiations
+                .take((name_id, buyer))
+                .ok_or(DNSError::NoNegotiation)?;
+            if negotiation.escrow != price {
+                return Err(DNSError::PriceMismatch);
+            }
+
+            self.settle_sale(name_id, domain, buyer, negotiation.escrow, Currency::Native)?;
+
+            self.env().emit_event(NegotiationClosed {
+                name_id,
+                buyer,
+                price: Some(negotiation.escrow),
+            });
+            Ok(())
+        }
+
+        // accept the owner's counter price, topping up the escrow with the
+        // transferred value. Escrow above the counter price is refunded.
+        // The price is the one the buyer agrees to, the owner may have
+        // countered again since.
+        #[ink(message, payable)]
+        pub fn accept_counter_offer(
+            &mut self,
+            name_id: DomainNameId,
+            price: u128,
+        ) -> Result<(), DNSError> {
+            let domain = self.live_domain(name_id)?;
+            let caller = self.env().caller();
+            self.ensure_transferable(name_id)?;
+
+            let negotiation = self
+                .negotiations
+                .take((name_id, caller))
+                .ok_or(DNSError::NoNegotiation)?;
+            if negotiation.countered_by != Some(domain.default_address) {
+                return Err(DNSError::NoCounterOffer);
+            }
+            if negotiation.counter_price.ok_or(DNSError::NoCounterOffer)? != price {
+                return Err(DNSError::PriceMismatch);
+            }
+
+            let funds = negotiation.escrow + self.env().transferred_value();
+            if funds < price {
+                return Err(DNSError::IncorrectPayment);
+            }
+            self.credit(caller, funds - price);
+
+            self.settle_sale(name_id, domain, caller, price, Currency::Native)?;
+
+            self.env().emit_event(NegotiationClosed {
+                name_id,
+                buyer: caller,
+                price: Some(price),
+            });
+            Ok(())
+        }
+
+        // reject a proposal, owner only. The escrow becomes withdrawable.
+        #[ink(message)]
+        pub fn reject_proposal(
+            &mut self,
+            name_id: DomainNameId,
+            buyer: AccountId,
+        ) -> Result<(), DNSError> {
+            self.owned_domain(name_id)?;
+            let negotiation = self
+                .negotiations
+                .take((name_id, buyer))
+                .ok_or(DNSError::NoNegotiation)?;
+            self.credit(buyer, negotiation.escrow);
+
+            self.env().emit_event(NegotiationClosed {
+                name_id,
+                buyer,
+                price: None,
+            });
+            Ok(())
+        }
+
+        // withdraw the caller's proposal and get the escrow back
+        #[ink(message)]
+        pub fn withdraw_proposal(&mut self, name_id: DomainNameId) -> Result<(), DNSError> {
+            let caller = self.env().caller();
+            let negotiation = self
+                .negotiations
+                .take((name_id, caller))
+                .ok_or(DNSError::NoNegotiation)?;
+            self.pay(caller, negotiation.escrow)?;
+
+            self.env().emit_event(NegotiationClosed {
+                name_id,
+                buyer: caller,
+                price: None,
+            });
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn get_negotiation(
+            &self,
+            name_id: DomainNameId,
+            buyer: AccountId,
+        ) -> Option<Negotiation> {
+            self.negotiations.get((name_id, buyer))
+        }
+
+        // place an escrowed bid on a domain name, the transferred value is the
+        // bid. A previous bid of the caller on the same name is replaced and
+        // its funds become withdrawable.
+        #[ink(message, payable)]
+        pub fn place_standing_bid(
+            &mut self,
+            name_id: DomainNameId,
+            expires_at: Timestamp,
+        ) -> Result<(), DNSError> {
+            let domain = self.live_domain(name_id)?;
+            let caller = self.env().caller();
+            let amount = self.env().transferred_value();
+
+            if domain.default_address == caller {
+                return Err(DNSError::SameOwner);
+            }
+            if amount == 0 {
+                return Err(DNSError::BidTooLow);
+            }
+            if expires_at <= self.env().block_timestamp() {
+                return Err(DNSError::InvalidEndTime);
+            }
+
+            let mut bids = self.standing_bids.get(name_id).unwrap_or_default();
+            if let Some(index) = bids.iter().position(|bid| bid.bidder == caller) {
+                let previous = bids.swap_remove(index);
+                self.credit(caller, previous.amount);
+            }
+
+            // expired bids are refunded to make room
+            let now = self.env().block_timestamp();
+            let (live, expired): (Vec<_>, Vec<_>) =
+                bids.into_iter().partition(|bid| bid.expires_at > now);
+            bids = live;
+            for bid in expired {
+                self.credit(bid.bidder, bid.amount);
+            }
+
+            // a full book only takes a bid that outbids its lowest one
+            if bids.len() >= MAX_STANDING_BIDS {
+                let (index, lowest) = bids
+                    .iter()
+                    .enumerate()
+                    .min_by_key(|(_, bid)| bid.amount)
+                    .ok_or(DNSError::BidBookFull)?;
+                if amount <= lowest.amount {
+                    return Err(DNSError::BidBookFull);
+                }
+                let outbid = bids.swap_remove(index);
+                self.credit(outbid.bidder, outbid.amount);
+            }
+            bids.push(StandingBid {
+                bidder: caller,
+                amount,
+                expires_at,
+            });
+            self.standing_bids.insert(name_id, &bids);
+
+            self.env().emit_event(StandingBidPlaced {
+                name_id,
+                bidder: caller,
+                amount,
+                expires_at,
+            });
+            Ok(())
+        }
+
+        // cancel the caller's standing bid and get the funds back
+        #[ink(message)]
+        pub fn cancel_standing_bid(&mut self, name_id: DomainNameId) -> Result<(), DNSError> {
+            let caller = self.env().caller();
+            let bid = self.take_standing_bid(name_id, caller)?;
+            self.pay(caller, bid.amount)?;
+
+            self.env().emit_event(StandingBidCancelled {
+                name_id,
+                bidder: caller,
+            });
+            Ok(())
+        }
+
+        // accept an outstanding standing bid of the given amount, selling the
+        // name to the bidder
+        #[ink(message)]
+        pub fn accept_standing_bid(
+            &mut self,
+            name_id: DomainNameId,
+            bidder: AccountId,
+            amount: u128,
+        ) -> Result<(), DNSError> {
+            let domain = self.owned_domain(name_id)?;
+            self.ensure_transferable(name_id)?;
+            if bidder == domain.default_address {
+                return Err(DNSError::SameOwner);
+            }
+
+            let bid = self.take_standing_bid(name_id, bidder)?;
+            if bid.expires_at <= self.env().block_timestamp() {
+                return Err(DNSError::BidExpired);
+            }
+            // the bidder may have replaced the bid after the owner saw it
+            if bid.amount != amount {
+                return Err(DNSError::PriceMismatch);
+            }
+
+            self.settle_sale(name_id, domain, bidder, bid.amount, Currency::Native)
+        }
+
+        #[ink(message)]
+        pub fn get_standing_bids(&self, name_id: DomainNameId) -> Vec<StandingBid> {
+            self.standing_bids.get(name_id).unwrap_or_default()
+        }
+
+        // offer a lease-to-own plan to a buyer: a deposit followed by
+        // installments due every interval
+        #[ink(message)]
+        pub fn offer_lease(
+            &mut self,
+            name_id: DomainNameId,
+            buyer: AccountId,
+            deposit: u128,
+            installment: u128,
+            installments: u32,
+            interval: Timestamp,
+        ) -> Result<(), DNSError> {
+            let mut domain = self.owned_domain(name_id)?;
+            self.ensure_transferable(name_id)?;
+            if buyer == domain.default_address {
+                return Err(DNSError::SameOwner);
+            }
+            if installments == 0 || interval == 0 {
+                return Err(DNSError::InvalidLeaseTerms);
+            }
+
+            domain.clear_offer();
+            self.domain_name.insert(name_id, &domain);
+            self.rentals.remove(name_id);
+            self.leases.insert(
+                name_id,
+                &Lease {
+                    seller: domain.default_address,
+                    buyer,
+                    deposit,
+                    installment,
+                    installments_left: installments,
+                    interval,
+                    next_due: None,
+                    paid: 0,
+                },
+            );
+
+            self.env().emit_event(LeaseOffered {
+                name_id,
+                buyer,
+                deposit,
+                installment,
+                installments,
+                interval,
+            });
+            Ok(())
+        }
+
+        // withdraw a lease offer the buyer has not accepted yet, seller only
+        #[ink(message)]
+        pub fn cancel_lease_offer(&mut self, name_id: DomainNameId) -> Result<(), DNSError> {
+            let lease = self.leases.get(name_id).ok_or(DNSError::NoLease)?;
+            if lease.seller != self.env().caller() {
+                return Err(DNSError::NotAOwner);
+            }
+            if lease.next_due.is_some() {
+                return Err(DNSError::LeaseAlreadyStarted);
+            }
+            self.leases.remove(name_id);
+
+            self.env().emit_event(LeaseEnded {
+                name_id,
+                buyer: lease.buyer,
+                completed: false,
+            });
+            Ok(())
+        }
+
+        // accept a lease offer by paying the deposit. The buyer controls
+        // resolution from now on while the seller keeps the title.
+        #[ink(message, payable)]
+        pub fn accept_lease(&mut self, name_id: DomainNameId) -> Result<(), DNSError> {
+            let mut lease = self.leases.get(name_id).ok_or(DNSError::NoLease)?;
+            let caller = self.env().caller();
+            self.live_domain(name_id)?;
+
+            if lease.buyer != caller {
+                return Err(DNSError::NotDesignatedBuyer);
+            }
+            if lease.next_due.is_some() {
+                return Err(DNSError::LeaseAlreadyStarted);
+            }
+            if self.env().transferred_value() != lease.deposit {
+                return Err(DNSError::IncorrectPayment);
+            }
+
+            lease.paid = lease.deposit;
+            lease.next_due = Some(self.env().block_timestamp() + lease.interval);
+            self.leases.insert(name_id, &lease);
+            self.rentals.remove(name_id);
+
+            self.env().emit_event(LeasePayment {
+                name_id,
+                buyer: caller,
+                amount: lease.deposit,
+                installments_left: lease.installments_left,
+            });
+            Ok(())
+        }
+
+        // pay the next installment, the last one transfers the title
+        #[ink(message, payable)]
+        pub fn pay_installment(&mut self, name_id: DomainNameId) -> Result<(), DNSError> {
+            let mut lease = self.leases.get(name_id).ok_or(DNSError::NoLease)?;
+            let caller = self.env().caller();
+
+            if lease.buyer != caller {
+                return Err(DNSError::NotDesignatedBuyer);
+            }
+            let next_due = lease.next_due.ok_or(DNSError::LeaseNotStarted)?;
+            if self.env().block_timestamp() > next_due {
+                return Err(DNSError::LeaseDefaulted);
+            }
+            // nothing more is paid into a lease whose registration lapsed
+            let domain = self.live_domain(name_id)?;
+            if self.env().transferred_value() != lease.installment {
+                return Err(DNSError::IncorrectPayment);
+            }
+
+            lease.paid += lease.installment;
+            lease.installments_left -= 1;
+
+            self.env().emit_event(LeasePayment {
+                name_id,
+                buyer: caller,
+                amount: lease.installment,
+                installments_left: lease.installments_left,
+            });
+
+            if lease.installments_left > 0 {
+                lease.next_due = Some(next_due + lease.interval);
+                self.leases.insert(name_id, &lease);
+                return Ok(());
+            }
+
+            // plan completed, sell the name from the title holder to the buyer
+            self.leases.remove(name_id);
+            self.settle_sale(name_id, domain, caller, lease.paid, Currency::Native)?;
+
+            self.env().emit_event(LeaseEnded {
+                name_id,
+                buyer: caller,
+                completed: true,
+            });
+            Ok(())
+        }
+
+        // end a lease after a missed installment, payments made so far become
+        // withdrawable by the seller. If the registration lapsed before the
+        // buyer missed an installment either party may end it and the buyer
+        // gets the payments back.
+        #[ink(message)]
+        pub fn reclaim_lease(&mut self, name_id: DomainNameId) -> Result<(), DNSError> {
+            let lease = self.leases.get(name_id).ok_or(DNSError::NoLease)?;
+            let caller = self.env().caller();
+            let now = self.env().block_timestamp();
+            let next_due = lease.next_due.ok_or(DNSError::LeaseNotStarted)?;
+            let domain = self
+                .domain_name
+                .get(name_id)
+                .ok_or(DNSError::DomainNotFound)?;
+            let lapsed = domain.is_expired(now) && domain.expires_at <= next_due;
+
+            if caller != lease.seller && !(lapsed && caller == lease.buyer) {
+                return Err(DNSError::NotAOwner);
+            }
+            if !lapsed && now <= next_due {
+                return Err(DNSError::PaymentNotOverdue);
+            }
+            self.leases.remove(name_id);
+
+            let refunded = if lapsed { lease.buyer } else { lease.seller };
+            self.credit(refunded, lease.paid);
+
+            self.env().emit_event(LeaseEnded {
+                name_id,
+                buyer: lease.buyer,
+                completed: false,
+            });
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn get_lease(&self, name_id: DomainNameId) -> Option<Lease> {
+            self.leases.get(name_id)
+        }
+
+        // offer a domain name for rent at price for duration, the renter
+        // controls resolution while the owner keeps the title
+        #[ink(message)]
+        pub fn offer_rental(
+            &mut self,
+            name_id: DomainNameId,
+            price: u128,
+            duration: Timestamp,
+        ) -> Result<(), DNSError> {
+            self.owned_domain(name_id)?;
+            self.ensure_transferable(name_id)?;
+            if duration == 0 {
+                return Err(DNSError::InvalidDuration);
+            }
+
+            self.rentals.insert(
+                name_id,
+                &Rental {
+                    price,
+                    duration,
+                    renter: None,
+                    end_time: 0,
+                },
+            );
+
+            self.env().emit_event(RentalOffered {
+                name_id,
+                price,
+                duration,
+            });
+            Ok(())
+        }
+
+        // withdraw a rental offer while the name is not rented, owner only
+        #[ink(message)]
+        pub fn cancel_rental_offer(&mut self, name_id: DomainNameId) -> Result<(), DNSError> {
+            self.owned_domain(name_id)?;
+            self.ensure_transferable(name_id)?;
+            if self.rentals.take(name_id).is_none() {
+                return Err(DNSError::NoRental);
+            }
+            Ok(())
+        }
+
+        // rent a domain name offered for rent, paying the rental price
+        #[ink(message, payable)]
+        pub fn rent(&mut self, name_id: DomainNameId) -> Result<(), DNSError> {
+            let mut rental = self.rentals.get(name_id).ok_or(DNSError::NoRental)?;
+            let mut domain = self.live_domain(name_id)?;
+            let caller = self.env().caller();
+            let now = self.env().block_timestamp();
+
+            if domain.default_address == caller {
+                return Err(DNSError::SameOwner);
+            }
+            // not while rented already, auctioned or leased
+            self.ensure_transferable(name_id)?;
+            if self.env().transferred_value() != rental.price {
+                return Err(DNSError::IncorrectPayment);
+            }
+
+            rental.renter = Some(caller);
+            rental.end_time = now + rental.duration;
+            self.rentals.insert(name_id, &rental);
+
+            // the name can't be sold while rented
+            if domain.offer_state != State::NotOffering {
+                domain.clear_offer();
+                self.domain_name.insert(name_id, &domain);
+                self.env().emit_event(DomainDelisted { name_id });
+            }
+
+            // the protocol fee applies to rental payments as well
+            let fee = rental.price * self.fee_bps as u128 / BPS_DENOMINATOR;
+            self.treasury += fee;
+            self.pay(domain.default_address, rental.price - fee)?;
+
+            self.env().emit_event(DomainRented {
+                name_id,
+                renter: caller,
+                price: rental.price,
+                end_time: rental.end_time,
+            });
+            Ok(())
+        }
+
+        // set the address the name resolves to, current controller only
+        #[ink(message)]
+        pub fn set_resolved_address(
+            &mut self,
+            name_id: DomainNameId,
+            address: AccountId,
+        ) -> Result<(), DNSError> {
+            let controller = self.ensure_controller(name_id)?;
+            self.resolved_address
+                .insert((name_id, controller), &address);
+
+            self.env().emit_event(ResolvedAddressChanged {
+                name_id,
+                controller,
+                address,
+            });
+            Ok(())
+        }
+
+        // set a text record, an empty value removes it. Current controller only.
+        #[ink(message)]
+        pub fn set_record(
+            &mut self,
+            name_id: DomainNameId,
+            key: String,
+            value: String,
+        ) -> Result<(), DNSError> {
+            let controller = self.ensure_controller(name_id)?;
+            let record_key = (name_id, controller, key.clone());
+            if value.is_empty() {
+                self.records.remove(&record_key);
+            } else {
+                self.records.insert(&record_key, &value);
+            }
+
+            self.env().emit_event(RecordChanged {
+                name_id,
+                controller,
+                key,
+                value,
+            });
+            Ok(())
+        }
+
+        // address a name resolves to
+        #[ink(message)]
+        pub fn resolve(&self, name: String) -> Option<AccountId> {
+            let name = normalize_name(&name).ok()?;
+            let name_id = self.name_to_id.get(&name)?;
+            let controller = self.controller_of(name_id)?;
+            Some(
+                self.resolved_address
+                    .get((name_id, controller))
+                    .unwrap_or(controller),
+            )
+        }
+
+        #[ink(message)]
+        pub fn get_record(&self, name_id: DomainNameId, key: String) -> Option<String> {
+            let controller = self.controller_of(name_id)?;
+            self.records.get((name_id, controller, key))
+        }
+
+        // account currently controlling resolution of a name
+        #[ink(message)]
+        pub fn get_controller(&self, name_id: DomainNameId) -> Option<AccountId> {
+            self.controller_of(name_id)
+        }
+
+        #[ink(message)]
+        pub fn get_rental(&self, name_id: DomainNameId) -> Option<Rental> {
+            self.rentals.get(name_id)
+        }
+
+        // put a domain name up for english auction until end_time
+        #[ink(message)]
+        pub fn start_auction(
+            &mut self,
+            name_id: DomainNameId,
+            reserve_price: u128,
+            end_time: Timestamp,
+        ) -> Result<(), DNSError> {
+            let mut domain = self.owned_domain(name_id)?;
+            self.ensure_transferable(name_id)?;
+            if end_time <= self.env().block_timestamp() {
+                return Err(DNSError::InvalidEndTime);
+            }
+
+            // the name can't be sold at a fixed price or rented while auctioned
+            domain.clear_offer();
+            self.domain_name.insert(name_id, &domain);
+            self.rentals.remove(name_id);
+
+            let auction = Auction {
+                seller: domain.default_address,
+                reserve_price,
+                end_time,
+                highest_bidder: None,
+                highest_bid: 0,
+            };
+            self.auctions.insert(name_id, &auction);
+
+            self.env().emit_event(AuctionStarted {
+                name_id,
+                seller: auction.seller,
+                reserve_price,
+                end_time,
+            });
+            Ok(())
+        }
+
+        // bid on an auctioned domain name, the transferred value is the bid
+        #[ink(message, payable)]
+        pub fn bid(&mut self, name_id: DomainNameId) -> Result<(), DNSError> {
+            let mut auction = self.auctions.get(name_id).ok_or(DNSError::NoAuction)?;
+            let caller = self.env().caller();
+            let now = self.env().block_timestamp();
+            let amount = self.env().transferred_value();
+
+            if now >= auction.end_time {
+                return Err(DNSError::AuctionEnded);
+            }
+            if caller == auction.seller {
+                return Err(DNSError::SameOwner);
+            }
+            if amount < auction.reserve_price || amount <= auction.highest_bid {
+                return Err(DNSError::BidTooLow);
+            }
+
+            // outbid funds become withdrawable
+            if let Some(previous) = auction.highest_bidder {
+                self.credit(previous, auction.highest_bid);
+            }
+            auction.highest_bidder = Some(caller);
+            auction.highest_bid = amount;
+
+            // anti-sniping: late bids extend the auction
+            if auction.end_time - now < AUCTION_EXTENSION {
+                auction.end_time = now + AUCTION_EXTENSION;
+            }
+            self.auctions.insert(name_id, &auction);
+
+            self.env().emit_event(BidPlaced {
+                name_id,
+                bidder: caller,
+                amount,
+                end_time: auction.end_time,
+            });
+            Ok(())
+        }
+
+        // settle an ended auction, anyone may call this
+        #[ink(message)]
+        pub fn settle_auction(&mut self, name_id: DomainNameId) -> Result<(), DNSError> {
+            let auction = self.auctions.get(name_id).ok_or(DNSError::NoAuction)?;
+            if self.env().block_timestamp() < auction.end_time {
+                return Err(DNSError::AuctionNotEnded);
+            }
+            self.auctions.remove(name_id);
+
+            if let Some(winner) = auction.highest_bidder {
+                match self.live_domain(name_id) {
+                    Ok(domain) => self.settle_sale(
+                        name_id,
+                        domain,
+                        winner,
+                        auction.highest_bid,
+                        Currency::Native,
+                    )?,
+                    // the registration lapsed during the auction, refund the bid
+                    Err(_) => self.credit(winner, auction.highest_bid),
+                }
+            }
+
+            self.env().emit_event(AuctionSettled {
+                name_id,
+                winner: auction.highest_bidder,
+                price: auction.highest_bid,
+            });
+            Ok(())
+        }
+
+        // cancel an auction that has not received any bid
+        #[ink(message)]
+        pub fn cancel_auction(&mut self, name_id: DomainNameId) -> Result<(), DNSError> {
+            self.owned_domain(name_id)?;
+            let auction = self.auctions.get(name_id).ok_or(DNSError::NoAuction)?;
+            if auction.highest_bidder.is_some() {
+                return Err(DNSError::AuctionHasBids);
+            }
+            self.auctions.remove(name_id);
+
+            self.env().emit_event(AuctionSettled {
+                name_id,
+                winner: None,
+                price: 0,
+            });
+            Ok(())
+        }
+
+        // withdraw refunded funds credited to the caller
+        #[ink(message)]
+        pub fn withdraw(&mut self) -> Result<(), DNSError> {
+            let caller = self.env().caller();
+            let amount = self.pending_returns.get(caller).unwrap_or_default();
+            if amount == 0 {
+                return Err(DNSError::NothingToWithdraw);
+            }
+            self.pending_returns.remove(caller);
+            self.pay(caller, amount)?;
+
+            self.env().emit_event(Withdrawn {
+                account: caller,
+                amount,
+            });
+            Ok(())
+        }
+
+        // commit a sealed bid for an unregistered name, the transferred value is
+        // the deposit and must cover the bid. The first commit opens the auction.
+        // The deposit must cover a year of registration so that auctions can't
+        // be opened for free to block a name. Like a registration the bid needs
+        // a matured commitment to the name, so a registration can't be blocked
+        // by opening an auction once its name is revealed.
+        #[ink(message, payable)]
+        pub fn commit_sealed_bid(
+            &mut self,
+            name: String,
+            secret: [u8; 32],
+            hash: Hash,
+        ) -> Result<(), DNSError> {
+            self.take_commitment(&name, secret)?;
+            let name = normalize_name(&name)?;
+            self.ensure_open_tld(&name)?;
+            if self.reserved_names.contains(&name) {
+                return Err(DNSError::NameReserved);
+            }
+            if !self.name_available(&name) {
+                return Err(DNSError::DomainAlreadyOwned);
+            }
+            let caller = self.env().caller();
+            let now = self.env().block_timestamp();
+
+            let auction = match self.sealed_auctions.get(&name) {
+                Some(auction) => auction,
+                None => {
+                    let commit_end = now + SEALED_COMMIT_PERIOD;
+                    SealedAuction {
+                        commit_end,
+                        reveal_end: commit_end + SEALED_REVEAL_PERIOD,
+                        highest_bidder: None,
+                        highest_bid: 0,
+                        highest_deposit: 0,
+                        second_bid: 0,
+                    }
+                }
+            };
+            if now >= auction.commit_end {
+                return Err(DNSError::NotInCommitPhase);
+            }
+
+            let key = (name.clone(), caller);
+            if self.sealed_bids.contains(&key) {
+                return Err(DNSError::BidAlreadyCommitted);
+            }
+
+            let deposit = self.env().transferred_value();
+            if deposit == 0 || deposit < self.registration_price(&name, YEAR) {
+                return Err(DNSError::BidTooLow);
+            }
+            self.sealed_bids.insert(&key, &SealedBid { hash, deposit });
+            self.sealed_auctions.insert(&name, &auction);
+
+            self.env().emit_event(SealedBidCommitted {
+                name,
+                bidder: caller,
+                deposit,
+            });
+            Ok(())
+        }
+
+        // reveal a committed sealed bid. Losing deposits become withdrawable.
+        #[ink(message)]
+        pub fn reveal_sealed_bid(
+            &mut self,
+            name: String,
+            bid: u128,
+            salt: [u8; 32],
+        ) -> Result<(), DNSError> {
+            // the bid was committed over the name exactly as given
+            let caller = self.env().caller();
+            let hash = self.sealed_bid_hash(name.clone(), caller, bid, salt);
+            let name = normalize_name(&name)?;
+            let mut auction = self.sealed_auctions.get(&name).ok_or(DNSError::NoAuction)?;
+            let now = self.env().block_timestamp();
+
+            if now < auction.commit_end || now >= auction.reveal_end {
+                return Err(DNSError::NotInRevealPhase);
+            }
+
+            let key = (name.clone(), caller);
+            let sealed = self.sealed_bids.get(&key).ok_or(DNSError::NoSealedBid)?;
+            if hash != sealed.hash {
+                return Err(DNSError::InvalidReveal);
+            }
+            self.sealed_bids.remove(&key);
+
+            // a bid not covered by its deposit is refunded and ignored
+            if bid > sealed.deposit {
+                self.credit(caller, sealed.deposit);
+            } else if auction.highest_bidder.is_none() || bid > auction.highest_bid {
+                if let Some(previous) = auction.highest_bidder {
+                    self.credit(previous, auction.highest_deposit);
+                }
+                auction.second_bid = auction.highest_bid;
+                auction.highest_bidder = Some(caller);
+                auction.highest_bid = bid;
+                auction.highest_deposit = sealed.deposit;
+            } else {
+                if bid > auction.second_bid {
+                    auction.second_bid = bid;
+                }
+                self.credit(caller, sealed.deposit);
+            }
+            self.sealed_auctions.insert(&name, &auction);
+
+            self.env().emit_event(SealedBidRevealed {
+                name,
+                bidder: caller,
+                bid,
+            });
+            Ok(())
+        }
+
+        // award the name to the highest bidder at the second-highest price,
+        // anyone may call this once the reveal phase is over. The registration
+        // price for a year is the reserve: a winning bid below it is refunded
+        // and the name isn't registered.
+        #[ink(message)]
+        pub fn finalize_sealed_auction(&mut self, name: String) -> Result<(), DNSError> {
+            let name = normalize_name(&name)?;
+            let auction = self.sealed_auctions.get(&name).ok_or(DNSError::NoAuction)?;
+            if self.env().block_timestamp() < auction.reveal_end {
+                return Err(DNSError::AuctionNotEnded);
+            }
+            self.sealed_auctions.remove(&name);
+
+            let reserve = self.registration_price(&name, YEAR);
+            let price = auction.second_bid.max(reserve);
+            let mut winner = None;
+            if let Some(bidder) = auction.highest_bidder {
+                if auction.highest_bid >= reserve {
+                    self.credit(bidder, auction.highest_deposit - price);
+                    self.treasury += price;
+                    self.register_name(name.clone(), bidder, State::NotOffering, 0, YEAR)?;
+                    winner = Some(bidder);
+                } else {
+                    self.credit(bidder, auction.highest_deposit);
+                }
+            }
+
+            self.env().emit_event(SealedAuctionFinalized {
+                name,
+                winner,
+                price,
+            });
+            Ok(())
+        }
+
+        // get back the deposit of a sealed bid that was never revealed
+        #[ink(message)]
+        pub fn reclaim_sealed_bid(&mut self, name: String) -> Result<(), DNSError> {
+            let name = normalize_name(&name)?;
+            let caller = self.env().caller();
+            let key = (name.clone(), caller);
+            let sealed = self.sealed_bids.get(&key).ok_or(DNSError::NoSealedBid)?;
+
+            // still revealable while the auction is in its reveal phase
+            if let Some(auction) = self.sealed_auctions.get(&name) {
+                if self.env().block_timestamp() < auction.reveal_end {
+                    return Err(DNSError::AuctionNotEnded);
+                }
+            }
+            self.sealed_bids.remove(&key);
+            self.pay(caller, sealed.deposit)
+        }
+
+        // hash to commit for a sealed bid by bidder
+        #[ink(message)]
+        pub fn sealed_bid_hash(
+            &self,
+            name: String,
+            bidder: AccountId,
+            bid: u128,
+            salt: [u8; 32],
+        ) -> Hash {
+            Hash::from(
+                self.env()
+                    .hash_encoded::<Blake2x256, _>(&(name, bidder, bid, salt)),
+            )
+        }
+
+        #[ink(message)]
+        pub fn get_sealed_auction(&self, name: String) -> Option<SealedAuction> {
+            let name = normalize_name(&name).ok()?;
+            self.sealed_auctions.get(&name)
+        }
+
+        #[ink(message)]
+        pub fn get_auction(&self, name_id: DomainNameId) -> Option<Auction> {
+            self.auctions.get(name_id)
+        }
+
+        #[ink(message)]
+        pub fn get_pending_return(&self, account_id: AccountId) -> u128 {
+            self.pending_returns.get(account_id).unwrap_or_default()
+        }
+
+        #[ink(message)]
+        pub fn get_owner_domain_name(&self) -> Vec<DomainName> {
+            let mut domain_name: Vec<DomainName> = Vec::new();
+            let caller = self.env().caller();
+
+            for _item in 0..self.domain_name_id {
+                if let Some(value) = self.domain_name.get(_item) {
+                    // names in grace or redemption are still listed for renewal
+                    if value.default_address == caller
+                        && self.phase_of(&value) != NamePhase::Available
+                    {
+                        domain_name.push(self.effective_domain(value));
+                    }
+                }
+            }
+
+            domain_name
+        }
+
+        // set the royalty taken on resales, original registrant only. Once the
+        // name has changed hands the royalty can only be lowered, a later
+        // owner has priced the name with the royalty it bought it under.
+        #[ink(message)]
+        pub fn set_royalty(
+            &mut self,
+            name_id: DomainNameId,
+            royalty_bps: u16,
+        ) -> Result<(), DNSError> {
+            let mut domain = self.live_domain(name_id)?;
+            if domain.registrant != self.env().caller() {
+                return Err(DNSError::NotRegistrant);
+            }
+            if royalty_bps > MAX_ROYALTY_BPS {
+                return Err(DNSError::InvalidRoyalty);
+            }
+            if domain.default_address != domain.registrant && royalty_bps > domain.royalty_bps {
+                return Err(DNSError::InvalidRoyalty);
+            }
+
+            domain.royalty_bps = royalty_bps;
+            self.domain_name.insert(name_id, &domain);
+
+            self.env().emit_event(RoyaltyChanged {
+                name_id,
+                royalty_bps,
+            });
+            Ok(())
+        }
+
+        // royalty receiver and amount for a sale at sale_price (PSP34/EIP-2981 style)
+        #[ink(message)]
+        pub fn royalty_info(
+            &self,
+            name_id: DomainNameId,
+            sale_price: u128,
+        ) -> Result<(AccountId, u128), DNSError> {
+            let domain = self.live_domain(name_id)?;
+            Ok(Self::royalty_of(&domain, sale_price))
+        }
+
+        // set the yearly registration price per name length, owner only.
+        // prices[i] applies to names of i + 1 characters, the last price to
+        // all longer names, so shorter names are usually priced higher.
+        #[ink(message)]
+        pub fn set_price_schedule(&mut self, prices: Vec<u128>) -> Result<(), DNSError> {
+            self.ensure_owner()?;
+            if prices.len() > MAX_PRICE_TIERS {
+                return Err(DNSError::InvalidPriceSchedule);
+            }
+            self.price_schedule = prices.clone();
+
+            self.env().emit_event(PriceScheduleChanged { prices });
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn get_price_schedule(&self) -> Vec<u128> {
+            self.price_schedule.clone()
+        }
+
+        // price of registering or renewing name for duration
+        #[ink(message)]
+        pub fn quote_registration(
+            &self,
+            name: String,
+            duration: Timestamp,
+        ) -> Result<u128, DNSError> {
+            let name = normalize_name(&name)?;
+            if duration == 0 || duration > MAX_REGISTRATION_DURATION {
+                return Err(DNSError::InvalidDuration);
+            }
+            Ok(self.registration_price(&name, duration))
+        }
+
+        // price of registering or renewing name for duration in currency
+        #[ink(message)]
+        pub fn quote_registration_in(
+            &self,
+            name: String,
+            duration: Timestamp,
+            currency: Currency,
+        ) -> Result<u128, DNSError> {
+            let price = self.quote_registration(name, duration)?;
+            match currency {
+                Currency::Native => Ok(price),
+                Currency::Psp22(token) => {
+                    self.ensure_accepted(currency)?;
+                    self.token_amount(token, price)
+                }
+            }
+        }
+
+        // configure what happens after a registration expires, owner only
+        #[ink(message)]
+        pub fn set_expiry_periods(
+            &mut self,
+            grace_period: Timestamp,
+            redemption_period: Timestamp,
+            redemption_fee: u128,
+        ) -> Result<(), DNSError> {
+            self.ensure_owner()?;
+            self.grace_period = grace_period;
+            self.redemption_period = redemption_period;
+            self.redemption_fee = redemption_fee;
+
+            self.env().emit_event(ExpiryPeriodsChanged {
+                grace_period,
+                redemption_period,
+                redemption_fee,
+            });
+            Ok(())
+        }
+
+        // grace period, redemption period and redemption fee
+        #[ink(message)]
+        pub fn get_expiry_periods(&self) -> (Timestamp, Timestamp, u128) {
+            (
+                self.grace_period,
+                self.redemption_period,
+                self.redemption_fee,
+            )
+        }
+
+        // lifecycle phase of a registration
+        #[ink(message)]
+        pub fn get_name_phase(&self, name_id: DomainNameId) -> Option<NamePhase> {
+            self.domain_name
+                .get(name_id)
+                .map(|domain| self.phase_of(&domain))
+        }
+
+        // create `label.parent` and assign it to owner. The subdomain expires
+        // with its parent and needs no registration of its own.
+        #[ink(message)]
+        pub fn create_subdomain(
+            &mut self,
+            parent_id: DomainNameId,
+            label: String,
+            owner: AccountId,
+        ) -> Result<DomainNameId, DNSError> {
+            let parent = self.owned_domain(parent_id)?;
+            // a lessee resolves the name but doesn't hold its title yet
+            self.ensure_transferable(parent_id)?;
+            let label = Self::subdomain_label(&label)?;
+            let expires_at = parent.expires_at;
+            self.register_subdomain_name(parent_id, parent, label, owner, expires_at)
+        }
+
+        // open public subdomain registration on a name owned by the caller,
+        // or change its terms
+        #[ink(message)]
+        pub fn set_subdomain_registrar(
+            &mut self,
+            name_id: DomainNameId,
+            registrar: SubdomainRegistrar,
+        ) -> Result<(), DNSError> {
+            self.owned_domain(name_id)?;
+            // a lessee resolves the name but doesn't hold its title yet
+            self.ensure_transferable(name_id)?;
+            if registrar.duration == 0 || registrar.min_length > registrar.max_length {
+                return Err(DNSError::InvalidRegistrar);
+            }
+
+            self.registrars.insert(name_id, &registrar);
+
+            self.env().emit_event(SubdomainRegistrarSet {
+                name_id,
+                registrar: Some(registrar),
+            });
+            Ok(())
+        }
+
+        // stop public subdomain registration on a name owned by the caller
+        #[ink(message)]
+        pub fn close_subdomain_registrar(&mut self, name_id: DomainNameId) -> Result<(), DNSError> {
+            self.owned_domain(name_id)?;
+            if !self.registrars.contains(name_id) {
+                return Err(DNSError::RegistrarClosed);
+            }
+
+            self.registrars.remove(name_id);
+
+            self.env().emit_event(SubdomainRegistrarSet {
+                name_id,
+                registrar: None,
+            });
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn get_subdomain_registrar(&self, name_id: DomainNameId) -> Option<SubdomainRegistrar> {
+            self.registrars.get(name_id)
+        }
+
+        // claim `label.parent` from the parent's registrar. The parent owner is
+        // credited the price minus the protocol fee.
+        #[ink(message, payable)]
+        pub fn register_subdomain(
+            &mut self,
+            parent_id: DomainNameId,
+            label: String,
+        ) -> Result<DomainNameId, DNSError> {
+            let registrar = self
+                .registrars
+                .get(parent_id)
+                .ok_or(DNSError::RegistrarClosed)?;
+            let parent = self.live_domain(parent_id)?;
+            // proceeds would go to whoever holds the name during a lease
+            self.ensure_transferable(parent_id)?;
+            let label = Self::subdomain_label(&label)?;
+            let length = display_name(&label).chars().count() as u32;
+            if length < registrar.min_length || length > registrar.max_length {
+                return Err(DNSError::LabelLengthNotAllowed);
+            }
+
+            self.collect_registrar_price(&registrar, &parent)?;
+
+            let caller = self.env().caller();
+            let expires_at = parent.expires_at.min(
+                self.env()
+                    .block_timestamp()
+                    .saturating_add(registrar.duration),
+            );
+            self.register_subdomain_name(parent_id, parent, label, caller, expires_at)
+        }
+
+        // extend a subdomain by the registrar duration of its parent at the
+        // registrar price, capped at the parent expiry. After expiry only the
+        // previous owner may renew.
+        #[ink(message, payable)]
+        pub fn renew_subdomain(&mut self, name_id: DomainNameId) -> Result<(), DNSError> {
+            let mut domain = self
+                .domain_name
+                .get(name_id)
+                .ok_or(DNSError::DomainNotFound)?;
+            let parent_id = domain.parent.ok_or(DNSError::DomainNotFound)?;
+            match self.phase_of(&domain) {
+                NamePhase::Active => {}
+                NamePhase::GracePeriod | NamePhase::Redemption => {
+                    self.ensure_previous_owner(&domain)?;
+                }
+                NamePhase::Available => return Err(DNSError::DomainExpired),
+            }
+            let registrar = self
+                .registrars
+                .get(parent_id)
+                .ok_or(DNSError::RegistrarClosed)?;
+            let parent = self.live_domain(parent_id)?;
+            self.ensure_transferable(parent_id)?;
+            self.collect_registrar_price(&registrar, &parent)?;
+
+            let previous_expiry = domain.expires_at;
+            let from = previous_expiry.max(self.env().block_timestamp());
+            domain.expires_at = parent
+                .expires_at
+                .min(from.saturating_add(registrar.duration));
+            if domain.expires_at <= previous_expiry {
+                return Err(DNSError::InvalidDuration);
+            }
+            self.domain_name.insert(name_id, &domain);
+            self.extend_subdomains(name_id, previous_expiry, domain.expires_at);
+
+            self.env().emit_event(NameRenewed {
+                name_id,
+                expires_at: domain.expires_at,
+            });
+            Ok(())
+        }
+
+        // all subdomains below a domain name, depth first
+        #[ink(message)]
+        pub fn get_subdomains(&self, name_id: DomainNameId) -> Vec<DomainNameId> {
+            let mut found = Vec::new();
+            for child in self.subdomains.get(name_id).unwrap_or_default() {
+                found.push(child);
+                found.extend(self.get_subdomains(child));
+            }
+            found
+        }
+
+        // create a top-level domain names can be registered under, owner only
+        #[ink(message)]
+        pub fn create_tld(&mut self, tld: String, policy: TldPolicy) -> Result<(), DNSError> {
+            self.ensure_owner()?;
+            let tld = normalize_name(&tld)?;
+            if tld.contains('.') {
+                return Err(DNSError::NotUnderTld);
+            }
+            if self.tlds.contains(&tld) {
+                return Err(DNSError::TldExists);
+            }
+            Self::ensure_valid_policy(&policy)?;
+
+            self.tlds.insert(&tld, &policy);
+            self.tld_list.push(tld.clone());
+
+            self.env().emit_event(TldPolicySet { tld, policy });
+            Ok(())
+        }
+
+        // change the policy of a top-level domain. The tld manager may do so
+        // too but only the contract owner can change the manager.
+        #[ink(message)]
+        pub fn set_tld_policy(&mut self, tld: String, policy: TldPolicy) -> Result<(), DNSError> {
+            let tld = normalize_name(&tld)?;
+            let current = self.tlds.get(&tld).ok_or(DNSError::TldNotFound)?;
+            let caller = self.env().caller();
+            let is_manager = current.manager == Some(caller);
+            if caller != self.owner && (!is_manager || policy.manager != current.manager) {
+                return Err(DNSError::CallerIsNotOwner);
+            }
+            Self::ensure_valid_policy(&policy)?;
+
+            self.tlds.insert(&tld, &policy);
+
+            self.env().emit_event(TldPolicySet { tld, policy });
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn get_tld(&self, tld: String) -> Option<TldPolicy> {
+            let tld = normalize_name(&tld).ok()?;
+            self.tlds.get(&tld)
+        }
+
+        #[ink(message)]
+        pub fn get_tlds(&self) -> Vec<String> {
+            self.tld_list.clone()
+        }
+
+        // reserve names so they can't be registered, owner only
+        #[ink(message)]
+        pub fn reserve_names(&mut self, names: Vec<String>) -> Result<(), DNSError> {
+            self.ensure_owner()?;
+            let names = names
+                .iter()
+                .map(|name| normalize_name(name))
+                .collect::<Result<Vec<_>, _>>()?;
+            for name in &names {
+                self.reserved_names.insert(name, &());
+            }
+
+            self.env().emit_event(ReservationsChanged {
+                names,
+                reserved: true,
+            });
+            Ok(())
+        }
+
+        // release reserved names for registration, owner only
+        #[ink(message)]
+        pub fn unreserve_names(&mut self, names: Vec<String>) -> Result<(), DNSError> {
+            self.ensure_owner()?;
+            let names = names
+                .iter()
+                .map(|name| normalize_name(name))
+                .collect::<Result<Vec<_>, _>>()?;
+            for name in &names {
+                self.reserved_names.remove(name);
+            }
+
+            self.env().emit_event(ReservationsChanged {
+                names,
+                reserved: false,
+            });
+            Ok(())
+        }
+
+        // register a reserved name directly to an account, owner only
+        #[ink(message)]
+        pub fn assign_reserved_name(
+            &mut self,
+            name: String,
+            owner: AccountId,
+            duration: Timestamp,
+        ) -> Result<DomainNameId, DNSError> {
+            self.ensure_owner()?;
+            let name = normalize_name(&name)?;
+            // the owner may assign names under closed tlds or outside their
+            // length limits, but not outside any tld
+            self.tld_of(&name)?;
+            if self.reserved_names.take(&name).is_none() {
+                return Err(DNSError::NameNotReserved);
+            }
+            if duration == 0 || duration > MAX_REGISTRATION_DURATION {
+                return Err(DNSError::InvalidDuration);
+            }
+            self.register_name(name, owner, State::NotOffering, 0, duration)
+        }
+
+        #[ink(message)]
+        pub fn is_reserved(&self, name: String) -> bool {
+            normalize_name(&name).is_ok_and(|name| self.reserved_names.contains(&name))
+        }
+
+        // set the marketplace commission in basis points, owner only
+        #[ink(message)]
+        pub fn set_fee(&mut self, fee_bps: u16) -> Result<(), DNSError> {
+            self.ensure_owner()?;
+            if fee_bps as u128 > BPS_DENOMINATOR {
+                return Err(DNSError::InvalidFee);
+            }
+
+            let old_fee_bps = self.fee_bps;
+            self.fee_bps = fee_bps;
+
+            self.env().emit_event(FeeChanged {
+                old_fee_bps,
+                new_fee_bps: fee_bps,
+            });
+            Ok(())
+        }
+
+        // withdraw accrued fees from the treasury, owner only
+        #[ink(message)]
+        pub fn withdraw_treasury(&mut self, amount: u128, to: AccountId) -> Result<(), DNSError> {
+            self.ensure_owner()?;
+            if amount > self.treasury {
+                return Err(DNSError::InsufficientTreasury);
+            }
+
+            self.treasury -= amount;
+            self.pay(to, amount)?;
+
+            self.env().emit_event(TreasuryWithdrawn { to, amount });
+            Ok(())
+        }
+
+        // accept or refuse a PSP22 token for listings and registration fees, owner only
+        #[ink(message)]
+        pub fn set_token_accepted(
+            &mut self,
+            token: AccountId,
+            accepted: bool,
+        ) -> Result<(), DNSError> {
+            self.ensure_owner()?;
+            if accepted {
+                self.accepted_tokens.insert(token, &());
+            } else {
+                self.accepted_tokens.remove(token);
+            }
+
+            self.env()
+                .emit_event(TokenAcceptanceChanged { token, accepted });
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn is_token_accepted(&self, token: AccountId) -> bool {
+            self.accepted_tokens.contains(token)
+        }
+
+        // set the token units charged per native unit of registration price,
+        // scaled by RATE_DENOMINATOR. None stops registrations in the token.
+        // Owner only.
+        #[ink(message)]
+        pub fn set_token_rate(
+            &mut self,
+            token: AccountId,
+            rate: Option<u128>,
+        ) -> Result<(), DNSError> {
+            self.ensure_owner()?;
+            if let Some(rate) = rate {
+                self.token_rates.insert(token, &rate);
+            } else {
+                self.token_rates.remove(token);
+            }
+
+            self.env().emit_event(TokenRateChanged { token, rate });
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn get_token_rate(&self, token: AccountId) -> Option<u128> {
+            self.token_rates.get(token)
+        }
+
+        // withdraw accrued PSP22 fees from the treasury, owner only
+        #[ink(message)]
+        pub fn withdraw_token_treasury(
+            &mut self,
+            token: AccountId,
+            amount: u128,
+            to: AccountId,
+        ) -> Result<(), DNSError> {
+            self.ensure_owner()?;
+            let balance = self.token_treasury.get(token).unwrap_or_default();
+            if amount > balance {
+                return Err(DNSError::InsufficientTreasury);
+            }
+
+            self.token_treasury.insert(token, &(balance - amount));
+            self.psp22_transfer(token, to, amount)?;
+
+            self.env()
+                .emit_event(TokenTreasuryWithdrawn { token, to, amount });
+            Ok(())
+        }
+
+        // get PSP22 fees accrued in the treasury
+        #[ink(message)]
+        pub fn get_token_treasury(&self, token: AccountId) -> u128 {
+            self.token_treasury.get(token).unwrap_or_default()
+        }
+
+        #[ink(message)]
+        pub fn get_fee(&self) -> u16 {
+            self.fee_bps
+        }
+
+        // get fees accrued in the treasury
+        #[ink(message)]
+        pub fn get_treasury(&self) -> u128 {
+            self.treasury
+        }
+
+        // get a owner of contract
+        #[ink(message)]
+        pub fn get_owner(&self) -> AccountId {
+            self.owner
+        }
+
+        #[ink(message)]
+        pub fn get_no_of_name_claimed(&self) -> i32 {
+            self.no_of_claimed_names
+        }
+
+        // get domain name count
+        #[ink(message)]
+        pub fn get_owner_name_count(&self, account_id: AccountId) -> i32 {
+            self.owner_name_count.get(account_id).unwrap_or_default()
+        }
+
+        #[ink(message)]
+        pub fn is_claimed(&self, id: DomainNameId) -> bool {
+            self.claimed.get(id).unwrap_or_default()
+        }
+
+        fn ensure_owner(&self) -> Result<(), DNSError> {
+            if self.env().caller() != self.owner {
+                return Err(DNSError::CallerIsNotOwner);
+            }
+            Ok(())
+        }
+
+        // get a domain name that must be owned by the caller
+        fn owned_domain(&self, name_id: DomainNameId) -> Result<DomainName, DNSError> {
+            let domain = self.live_domain(name_id)?;
+            if domain.default_address != self.env().caller() {
+                return Err(DNSError::NotAOwner);
+            }
+            Ok(domain)
+        }
+
+        // consume the caller's commitment to name, it must be older than the
+        // minimum age and not older than the maximum
+        fn take_commitment(&mut self, name: &str, secret: [u8; 32]) -> Result<(), DNSError> {
+            let commitment = self.make_commitment(name.into(), self.env().caller(), secret);
+            let committed_at = self
+                .commitments
+                .take(commitment)
+                .ok_or(DNSError::NoCommitment)?;
+
+            let now = self.env().block_timestamp();
+            if now < committed_at + MIN_COMMITMENT_AGE {
+                return Err(DNSError::CommitmentTooNew);
+            }
+            if now > committed_at + MAX_COMMITMENT_AGE {
+                return Err(DNSError::CommitmentExpired);
+            }
+            Ok(())
+        }
+
+        // register a name to the caller, paying the registration price
+        fn register_paid(
+            &mut self,
+            name: String,
+            offer_state: State,
+            offer_price: u128,
+            duration: Timestamp,
+            currency: Currency,
+        ) -> Result<(), DNSError> {
+            // other listings go through their own messages and checks
+            if !matches!(offer_state, State::NotOffering | State::PublicOffering) {
+                return Err(DNSError::InvalidOfferState);
+            }
+            let name = normalize_name(&name)?;
+            self.ensure_open_tld(&name)?;
+            if self.reserved_names.contains(&name) {
+                return Err(DNSError::NameReserved);
+            }
+            // contested names are allocated by their sealed auction
+            if self.sealed_auctions.contains(&name) {
+                return Err(DNSError::DomainInAuction);
+            }
+            self.collect_registration_price(&name, duration, 0, currency)?;
+
+            let caller = self.env().caller();
+            self.register_name(name, caller, offer_state, offer_price, duration)?;
+            Ok(())
+        }
+
+        // register a new name to owner
+        fn register_name(
+            &mut self,
+            name: String,
+            owner: AccountId,
+            offer_state: State,
+            offer_price: u128,
+            duration: Timestamp,
+        ) -> Result<DomainNameId, DNSError> {
+            if !self.name_available(&name) {
+                return Err(DNSError::DomainAlreadyOwned);
+            }
+            // an expired registration of the name is released first
+            if let Some(old_id) = self.name_to_id.get(&name) {
+                self.release_name(old_id);
+            }
+
+            let name_id = self.next_domain_name_id();
+            // check name mustn't be already claimed
+            if self.claimed.get(name_id).unwrap_or_default() {
+                return Err(DNSError::NameAlreadyClaimed);
+            }
+
+            // insert name to owner
+            self.name_to_owner.insert(&name, &owner);
+            self.name_to_id.insert(&name, &name_id);
+
+            let domain_name = DomainName {
+                name,
+                offer_state,
+                offer_price,
+                offer_currency: Currency::Native,
+                offer_expires_at: None,
+                default_address: owner,
+                expires_at: self.env().block_timestamp() + duration,
+                registrant: owner,
+                royalty_bps: 0,
+                parent: None,
+            };
+
+            self.domain_name.insert(name_id, &domain_name);
+            self.claimed.insert(name_id, &true);
+            self.no_of_claimed_names += 1;
+
+            let name_count = self.owner_name_count.get(owner).unwrap_or_default();
+            self.owner_name_count.insert(owner, &(name_count + 1));
+
+            self.env().emit_event(NewNameClaimed { address: owner });
+            Ok(name_id)
+        }
+
+        // a name is available if it was never registered or its registration expired
+        fn name_available(&self, name: &String) -> bool {
+            match self.name_to_id.get(name) {
+                Some(name_id) => self
+                    .domain_name
+                    .get(name_id)
+                    .is_none_or(|domain| self.phase_of(&domain) == NamePhase::Available),
+                None => !self.name_to_owner.contains(name),
+            }
+        }
+
+        fn phase_of(&self, domain: &DomainName) -> NamePhase {
+            let now = self.env().block_timestamp();
+            let grace_end = domain.expires_at.saturating_add(self.grace_period);
+            if now < domain.expires_at {
+                NamePhase::Active
+            } else if now < grace_end {
+                NamePhase::GracePeriod
+            } else if now < grace_end.saturating_add(self.redemption_period) {
+                NamePhase::Redemption
+            } else {
+                NamePhase::Available
+            }
+        }
+
+        fn ensure_previous_owner(&self, domain: &DomainName) -> Result<(), DNSError> {
+            if domain.default_address != self.env().caller() {
+                return Err(DNSError::NotAOwner);
+            }
+            Ok(())
+        }
+
+        // drop an expired registration so its name can be registered again
+        fn release_name(&mut self, name_id: DomainNameId) {
+            if let Some(mut domain) = self.domain_name.get(name_id) {
+                let owner = domain.default_address;
+                let name_count = self.owner_name_count.get(owner).unwrap_or_default();
+                self.owner_name_count.insert(owner, &(name_count - 1));
+
+                self.name_to_owner.remove(&domain.name);
+                self.name_to_id.remove(&domain.name);
+                domain.clear_offer();
+                self.domain_name.insert(name_id, &domain);
+
+                // unlink it from its parent, its own subdomains lapsed with it
+                if let Some(parent_id) = domain.parent {
+                    let mut siblings = self.subdomains.get(parent_id).unwrap_or_default();
+                    siblings.retain(|&id| id != name_id);
+                    self.subdomains.insert(parent_id, &siblings);
+                }
+                self.subdomains.remove(name_id);
+                self.registrars.remove(name_id);
+            }
+            if self.claimed.get(name_id).unwrap_or_default() {
+                self.claimed.insert(name_id, &false);
+                self.no_of_claimed_names -= 1;
+            }
+        }
+
+        // take the registration price into the treasury and refund any
+        // overpayment. Token payments are pulled through the allowance at the
+        // token rate.
+        fn collect_registration_price(
+            &mut self,
+            name: &str,
+            duration: Timestamp,
+            penalty: u128,
+            currency: Currency,
+        ) -> Result<(), DNSError> {
+            let price = self.quote_registration(name.into(), duration)? + penalty;
+            let paid = self.env().transferred_value();
+            match currency {
+                Currency::Native => {
+                    if paid < price {
+                        return Err(DNSError::IncorrectPayment);
+                    }
+                    self.treasury += price;
+                    self.pay(self.env().caller(), paid - price)
+                }
+                Currency::Psp22(token) => {
+                    self.ensure_accepted(currency)?;
+                    if paid != 0 {
+                        return Err(DNSError::IncorrectPayment);
+                    }
+                    let amount = self.token_amount(token, price)?;
+                    let caller = self.env().caller();
+                    self.psp22_transfer_from(token, caller, self.env().account_id(), amount)?;
+                    let balance = self.token_treasury.get(token).unwrap_or_default();
+                    self.token_treasury.insert(token, &(balance + amount));
+                    Ok(())
+                }
+            }
+        }
+
+        // convert a native price to token units at the token rate
+        fn token_amount(&self, token: AccountId, price: u128) -> Result<u128, DNSError> {
+            let rate = self.token_rates.get(token).ok_or(DNSError::NoTokenRate)?;
+            price
+                .checked_mul(rate)
+                .map(|amount| amount / RATE_DENOMINATOR)
+                .ok_or(DNSError::NoTokenRate)
+        }
+
+        fn registration_price(&self, name: &str, duration: Timestamp) -> u128 {
+            // the label is priced by the schedule of its tld if it has one
+            let (label, tld_schedule) = match name.split_once('.') {
+                Some((label, tld)) => (
+                    label,
+                    self.tlds.get(tld).and_then(|policy| policy.price_schedule),
+                ),
+                None => (name, None),
+            };
+            let schedule = tld_schedule.as_ref().unwrap_or(&self.price_schedule);
+
+            // short names are priced on their unicode form, not the A-label
+            let length = display_name(label).chars().count();
+            let price_per_year = match schedule.last() {
+                Some(last) => *schedule.get(length.saturating_sub(1)).unwrap_or(last),
+                None => 0,
+            };
+            price_per_year * duration as u128 / YEAR as u128
+        }
+
+        // a registrable name is a single label under an existing, open tld
+        // whose length limits it respects
+        fn ensure_open_tld(&self, name: &str) -> Result<(), DNSError> {
+            let (label, policy) = self.tld_of(name)?;
+            if !policy.open {
+                return Err(DNSError::TldClosed);
+            }
+
+            let length = display_name(label).chars().count() as u32;
+            if length < policy.min_length || length > policy.max_length {
+                return Err(DNSError::LabelLengthNotAllowed);
+            }
+            Ok(())
+        }
+
+        // split a name into its label and the policy of the tld it is under
+        fn tld_of<'a>(&self, name: &'a str) -> Result<(&'a str, TldPolicy), DNSError> {
+            let (label, tld) = name.split_once('.').ok_or(DNSError::NotUnderTld)?;
+            let policy = self.tlds.get(tld).ok_or(DNSError::TldNotFound)?;
+            Ok((label, policy))
+        }
+
+        fn ensure_valid_policy(policy: &TldPolicy) -> Result<(), DNSError> {
+            let tiers = policy.price_schedule.as_ref().map_or(0, Vec::len);
+            if policy.min_length > policy.max_length || tiers > MAX_PRICE_TIERS {
+                return Err(DNSError::InvalidTldPolicy);
+            }
+            Ok(())
+        }
+
+        // get a domain name whose registration has not expired
+        fn live_domain(&self, name_id: DomainNameId) -> Result<DomainName, DNSError> {
+            let domain = self
+                .domain_name
+                .get(name_id)
+                .ok_or(DNSError::DomainNotFound)?;
+            if domain.is_expired(self.env().block_timestamp()) {
+                return Err(DNSError::DomainExpired);
+            }
+            Ok(domain)
+        }
+
+        // names under auction or lease can't change hands outside of it
+        fn ensure_transferable(&self, name_id: DomainNameId) -> Result<(), DNSError> {
+            if self.auctions.contains(name_id) {
+                return Err(DNSError::DomainInAuction);
+            }
+            if self.leases.contains(name_id) {
+                return Err(DNSError::DomainLeased);
+            }
+            let now = self.env().block_timestamp();
+            if let Some(rental) = self.rentals.get(name_id) {
+                if rental.active_renter(now).is_some() {
+                    return Err(DNSError::DomainRented);
+                }
+            }
+            Ok(())
+        }
+
+        // an active renter or the buyer of a started lease controls
+        // resolution, otherwise the owner does
+        fn controller_of(&self, name_id: DomainNameId) -> Option<AccountId> {
+            let domain = self.live_domain(name_id).ok()?;
+            let now = self.env().block_timestamp();
+            let renter = self
+                .rentals
+                .get(name_id)
+                .and_then(|rental| rental.active_renter(now));
+            let lessee = self
+                .leases
+                .get(name_id)
+                .filter(|lease| lease.next_due.is_some())
+                .map(|lease| lease.buyer);
+            Some(renter.or(lessee).unwrap_or(domain.default_address))
+        }
+
+        fn ensure_controller(&self, name_id: DomainNameId) -> Result<AccountId, DNSError> {
+            let controller = self
+                .controller_of(name_id)
+                .ok_or(DNSError::DomainNotFound)?;
+            if controller != self.env().caller() {
+                return Err(DNSError::NotController);
+            }
+            Ok(controller)
+        }
+
+        fn ensure_accepted(&self, currency: Currency) -> Result<(), DNSError> {
+            match currency {
+                Currency::Psp22(token) if !self.accepted_tokens.contains(token) => {
+                    Err(DNSError::TokenNotAccepted)
+                }
+                _ => Ok(()),
+            }
+        }
+
+        fn ensure_valid_expiry(&self, expires_at: Option<Timestamp>) -> Result<(), DNSError> {
+            match expires_at {
+                Some(expiry) if expiry <= self.env().block_timestamp() => {
+                    Err(DNSError::InvalidExpiry)
+                }
+                _ => Ok(()),
+            }
+        }
+
+        // domain name as seen by queries, lapsed listings report NotOffering
+        fn effective_domain(&self, mut domain: DomainName) -> DomainName {
+            if domain.offer_lapsed(self.env().block_timestamp()) {
+                domain.clear_offer();
+            }
+            domain
+        }
+
+        fn set_private_offer(
+            &mut self,
+            name_id: DomainNameId,
+            mut domain: DomainName,
+            buyer: AccountId,
+            price: u128,
+            currency: Currency,
+            expires_at: Option<Timestamp>,
+        ) -> Result<(), DNSError> {
+            if buyer == domain.default_address {
+                return Err(DNSError::SameOwner);
+            }
+            self.ensure_valid_expiry(expires_at)?;
+            self.ensure_accepted(currency)?;
+
+            domain.offer_state = State::PrivateOffering(buyer);
+            domain.offer_price = price;
+            domain.offer_currency = currency;
+            domain.offer_expires_at = expires_at;
+            self.domain_name.insert(name_id, &domain);
+
+            self.env().emit_event(PrivateOfferSet {
+                name_id,
+                buyer,
+                price,
+                currency,
+                expires_at,
+            });
+            Ok(())
+        }
+
+        fn take_standing_bid(
+            &mut self,
+            name_id: DomainNameId,
+            bidder: AccountId,
+        ) -> Result<StandingBid, DNSError> {
+            let mut bids = self.standing_bids.get(name_id).unwrap_or_default();
+            let index = bids
+                .iter()
+                .position(|bid| bid.bidder == bidder)
+                .ok_or(DNSError::NoStandingBid)?;
+            let bid = bids.swap_remove(index);
+
+            if bids.is_empty() {
+                self.standing_bids.remove(name_id);
+            } else {
+                self.standing_bids.insert(name_id, &bids);
+            }
+            Ok(bid)
+        }
+
+        // no royalty is due when the registrant sells the name themselves
+        fn royalty_of(domain: &DomainName, price: u128) -> (AccountId, u128) {
+            if domain.default_address == domain.registrant {
+                return (domain.registrant, 0);
+            }
+            let royalty = price * domain.royalty_bps as u128 / BPS_DENOMINATOR;
+            (domain.registrant, royalty)
+        }
+
+        fn current_price(&self, domain: &DomainName) -> Result<u128, DNSError> {
+            if domain.offer_lapsed(self.env().block_timestamp()) {
+                return Err(DNSError::OfferExpired);
+            }
+            match &domain.offer_state {
+                State::NotOffering => Err(DNSError::NotForSale),
+                State::PublicOffering | State::PrivateOffering(_) => Ok(domain.offer_price),
+                State::DutchOffering(listing) => Ok(listing.price_at(self.env().block_number())),
+            }
+        }
+
+        // pay the seller and hand the domain name over to the buyer
+        fn settle_sale(
+            &mut self,
+            name_id: DomainNameId,
+            domain: DomainName,
+            buyer: AccountId,
+            price: u128,
+            currency: Currency,
+        ) -> Result<(), DNSError> {
+            if domain.is_expired(self.env().block_timestamp()) {
+                return Err(DNSError::DomainExpired);
+            }
+            let seller = domain.default_address;
+
+            // protocol commission stays in the contract treasury
+            let fee = price * self.fee_bps as u128 / BPS_DENOMINATOR;
+            match currency {
+                Currency::Native => self.treasury += fee,
+                Currency::Psp22(token) => {
+                    if fee > 0 {
+                        self.psp22_transfer_from(token, buyer, self.env().account_id(), fee)?;
+                        let balance = self.token_treasury.get(token).unwrap_or_default();
+                        self.token_treasury.insert(token, &(balance + fee));
+                    }
+                }
+            }
+
+            // original registrant gets a royalty on secondary sales
+            let (registrant, royalty) = Self::royalty_of(&domain, price);
+            let royalty = royalty.min(price - fee);
+            if royalty > 0 {
+                self.pay_from(currency, buyer, registrant, royalty)?;
+                self.env().emit_event(RoyaltyPaid {
+                    name_id,
+                    registrant,
+                    amount: royalty,
+                });
+            }
+
+            self.pay_from(currency, buyer, seller, price - fee - royalty)?;
+            self.transfer_domain(name_id, domain, buyer);
+
+            let mut history = self.sale_history.get(name_id).unwrap_or_default();
+            if history.len() >= MAX_SALE_HISTORY {
+                history.remove(0);
+            }
+            history.push(SaleRecord {
+                buyer,
+                seller,
+                price,
+                currency,
+                block: self.env().block_number(),
+            });
+            self.sale_history.insert(name_id, &history);
+
+            self.env().emit_event(DomainSold {
+                name_id,
+                seller,
+                buyer,
+                price,
+            });
+            Ok(())
+        }
+
+        // pay in the given currency, native funds are already held by the
+        // contract while tokens are pulled from the payer's allowance
+        fn pay_from(
+            &self,
+            currency: Currency,
+            from: AccountId,
+            to: AccountId,
+            amount: u128,
+        ) -> Result<(), DNSError> {
+            match currency {
+                Currency::Native => self.pay(to, amount),
+                Currency::Psp22(token) => self.psp22_transfer_from(token, from, to, amount),
+            }
+        }
+
+        fn psp22_transfer_from(
+            &self,
+            token: AccountId,
+            from: AccountId,
+            to: AccountId,
+            amount: u128,
+        ) -> Result<(), DNSError> {
+            if amount == 0 {
+                return Ok(());
+            }
+            let result = build_call::<DefaultEnvironment>()
+                .call(token)
+                .exec_input(
+                    ExecutionInput::new(Selector::new(PSP22_TRANSFER_FROM))
+                        .push_arg(from)
+                        .push_arg(to)
+                        .push_arg(amount)
+                        .push_arg(Vec::<u8>::new()),
+                )
+                .returns::<Result<(), PSP22Error>>()
+                .try_invoke();
+            match result {
+                Ok(Ok(Ok(()))) => Ok(()),
+                _ => Err(DNSError::TokenTransferFailed),
+            }
+        }
+
+        fn psp22_transfer(
+            &self,
+            token: AccountId,
+            to: AccountId,
+            amount: u128,
+        ) -> Result<(), DNSError> {
+            let result = build_call::<DefaultEnvironment>()
+                .call(token)
+                .exec_input(
+                    ExecutionInput::new(Selector::new(PSP22_TRANSFER))
+                        .push_arg(to)
+                        .push_arg(amount)
+                        .push_arg(Vec::<u8>::new()),
+                )
+                .returns::<Result<(), PSP22Error>>()
+                .try_invoke();
+            match result {
+                Ok(Ok(Ok(()))) => Ok(()),
+                _ => Err(DNSError::TokenTransferFailed),
+            }
+        }
+
+        fn pay(&self, to: AccountId, amount: u128) -> Result<(), DNSError> {
+            if amount == 0 {
+                return Ok(());
+            }
+            self.env()
+                .transfer(to, amount)
+                .map_err(|_| DNSError::TransferFailed)
+        }
+
+        // add funds the account can later withdraw
+        fn credit(&mut self, account: AccountId, amount: u128) {
+            let balance = self.pending_returns.get(account).unwrap_or_default();
+            self.pending_returns.insert(account, &(balance + amount));
+        }
+
+        // move a domain name to a new owner and take it off the market
+        fn transfer_domain(
+            &mut self,
+            name_id: DomainNameId,
+            mut domain: DomainName,
+            new_owner: AccountId,
+        ) {
+            let old_owner = domain.default_address;
+
+            let old_count = self.owner_name_count.get(old_owner).unwrap_or_default();
+            self.owner_name_count.insert(old_owner, &(old_count - 1));
+            let new_count = self.owner_name_count.get(new_owner).unwrap_or_default();
+            self.owner_name_count.insert(new_owner, &(new_count + 1));
+
+            self.name_to_owner.insert(&domain.name, &new_owner);
+
+            domain.default_address = new_owner;
+            domain.clear_offer();
+            self.domain_name.insert(name_id, &domain);
+
+            // registrar terms and rental offers were set by the previous owner
+            self.registrars.remove(name_id);
+            let now = self.env().block_timestamp();
+            if self
+                .rentals
+                .get(name_id)
+                .is_some_and(|rental| rental.active_renter(now).is_none())
+            {
+                self.rentals.remove(name_id);
+            }
+            self.move_subdomains(name_id, old_owner, new_owner);
+        }
+
+        // subdomains the previous owner kept for themselves follow the parent
+        // to its new owner, those assigned to other accounts stay with them
+        fn move_subdomains(
+            &mut self,
+            name_id: DomainNameId,
+            old_owner: AccountId,
+            new_owner: AccountId,
+        ) {
+            for child_id in self.subdomains.get(name_id).unwrap_or_default() {
+                let Ok(child) = self.live_domain(child_id) else {
+                    continue;
+                };
+                if child.default_address == old_owner && self.ensure_transferable(child_id).is_ok()
+                {
+                    self.transfer_domain(child_id, child, new_owner);
+                }
+            }
+        }
+
+        // carry a renewal of a domain name over to the subdomains that share
+        // its expiry, those sold for a shorter term keep their own
+        fn extend_subdomains(
+            &mut self,
+            name_id: DomainNameId,
+            previous_expiry: Timestamp,
+            expires_at: Timestamp,
+        ) {
+            for child_id in self.subdomains.get(name_id).unwrap_or_default() {
+                if let Some(mut child) = self.domain_name.get(child_id) {
+                    if child.expires_at == previous_expiry {
+                        child.expires_at = expires_at;
+                        self.domain_name.insert(child_id, &child);
+                        self.extend_subdomains(child_id, previous_expiry, expires_at);
+                    }
+                }
+            }
+        }
+
+        // a subdomain label is a single normalized label
+        fn subdomain_label(label: &str) -> Result<String, DNSError> {
+            let label = normalize_name(label)?;
+            if label.contains('.') {
+                return Err(DNSError::InvalidCharacter);
+            }
+            Ok(label)
+        }
+
+        // take the registrar price, crediting the parent owner all but the
+        // protocol fee, and refund any overpayment
+        fn collect_registrar_price(
+            &mut self,
+            registrar: &SubdomainRegistrar,
+            parent: &DomainName,
+        ) -> Result<(), DNSError> {
+            let paid = self.env().transferred_value();
+            if paid < registrar.price {
+                return Err(DNSError::IncorrectPayment);
+            }
+            let fee = registrar.price * self.fee_bps as u128 / BPS_DENOMINATOR;
+            self.treasury += fee;
+            self.credit(parent.default_address, registrar.price - fee);
+            self.pay(self.env().caller(), paid - registrar.price)
+        }
+
+        // register `label.parent` to owner until expires_at
+        fn register_subdomain_name(
+            &mut self,
+            parent_id: DomainNameId,
+            parent: DomainName,
+            label: String,
+            owner: AccountId,
+            expires_at: Timestamp,
+        ) -> Result<DomainNameId, DNSError> {
+            let name = normalize_name(&format!("{}.{}", label, parent.name))?;
+
+            let mut children = self.subdomains.get(parent_id).unwrap_or_default();
+            if children.len() >= MAX_SUBDOMAINS {
+                return Err(DNSError::TooManySubdomains);
+            }
+
+            let duration = expires_at - self.env().block_timestamp();
+            let name_id = self.register_name(name, owner, State::NotOffering, 0, duration)?;
+            if let Some(mut domain) = self.domain_name.get(name_id) {
+                domain.parent = Some(parent_id);
+                self.domain_name.insert(name_id, &domain);
+            }
+            children.push(name_id);
+            self.subdomains.insert(parent_id, &children);
+
+            self.env().emit_event(SubdomainCreated {
+                parent_id,
+                name_id,
+                owner,
+            });
+            Ok(name_id)
+        }
+
+        #[inline]
+        fn next_domain_name_id(&mut self) -> DomainNameId {
+            let id = self.domain_name_id;
+            self.domain_name_id += 1;
             id
         }
     }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use ink::env::test;
+
+        type E = DefaultEnvironment;
+
+        const DAY: Timestamp = 24 * 60 * 60 * 1000;
+
+        fn accounts() -> test::DefaultAccounts<E> {
+            test::default_accounts::<E>()
+        }
+
+        fn contract_id() -> AccountId {
+            AccountId::from([0xff; 32])
+        }
+
+        // act as caller, transferring value with the call
+        fn call(caller: AccountId, value: u128) {
+            test::set_caller::<E>(caller);
+            test::set_value_transferred::<E>(value);
+        }
+
+        fn now() -> Timestamp {
+            ink::env::block_timestamp::<E>()
+        }
+
+        fn set_time(timestamp: Timestamp) {
+            test::set_block_timestamp::<E>(timestamp);
+        }
+
+        fn balance(account: AccountId) -> u128 {
+            test::get_account_balance::<E>(account).unwrap_or_default()
+        }
+
+        // contract owned by alice with an open `dot` tld
+        fn setup() -> DnsContract {
+            test::set_callee::<E>(contract_id());
+            test::set_account_balance::<E>(contract_id(), 1_000_000_000);
+            call(accounts().alice, 0);
+            let mut contract = DnsContract::new();
+            contract
+                .create_tld(
+                    "dot".into(),
+                    TldPolicy {
+                        price_schedule: None,
+                        min_length: 1,
+                        max_length: 63,
+                        open: true,
+                        manager: None,
+                    },
+                )
+                .unwrap();
+            contract
+        }
+
+        // register name to owner for a year through commit and reveal
+        fn register(contract: &mut DnsContract, name: &str, owner: AccountId) -> DomainNameId {
+            try_register(contract, name, owner).unwrap();
+            contract
+                .name_to_id
+                .get(normalize_name(name).unwrap())
+                .unwrap()
+        }
+
+        fn try_register(
+            contract: &mut DnsContract,
+            name: &str,
+            owner: AccountId,
+        ) -> Result<(), DNSError> {
+            let secret = [7; 32];
+            commit_to(contract, name, owner, secret);
+            set_time(now() + MIN_COMMITMENT_AGE);
+            contract.create_new_dns(
+                name.into(),
+                secret,
+                State::NotOffering,
+                0,
+                YEAR,
+                Currency::Native,
+            )
+        }
+
+        fn commit_to(contract: &mut DnsContract, name: &str, owner: AccountId, secret: [u8; 32]) {
+            call(owner, 0);
+            let commitment = contract.make_commitment(name.into(), owner, secret);
+            contract.commit(commitment).unwrap();
+        }
+
+        fn owner_of(contract: &DnsContract, name_id: DomainNameId) -> AccountId {
+            contract.get_domain(name_id).unwrap().default_address
+        }
+
+        #[ink::test]
+        fn buy_domain_pays_the_seller_the_listing_price() {
+            let a = accounts();
+            let mut contract = setup();
+            let name_id = register(&mut contract, "name.dot", a.bob);
+
+            call(a.charlie, 1_000);
+            assert_eq!(
+                contract.buy_domain(name_id, 1_000),
+                Err(DNSError::NotForSale)
+            );
+
+            call(a.bob, 0);
+            contract
+                .list_domain(name_id, 1_000, Currency::Native, None)
+                .unwrap();
+            call(a.charlie, 999);
+            assert_eq!(
+                contract.buy_domain(name_id, 1_000),
+                Err(DNSError::IncorrectPayment)
+            );
+
+            let bob_before = balance(a.bob);
+            call(a.charlie, 1_000);
+            contract.buy_domain(name_id, 1_000).unwrap();
+            assert_eq!(balance(a.bob) - bob_before, 1_000);
+            assert_eq!(owner_of(&contract, name_id), a.charlie);
+            assert_eq!(
+                contract.get_domain(name_id).unwrap().offer_state,
+                State::NotOffering
+            );
+        }
+
+        #[ink::test]
+        fn set_new_owner_takes_the_name_off_the_market() {
+            let a = accounts();
+            let mut contract = setup();
+            let name_id = register(&mut contract, "name.dot", a.bob);
+
+            call(a.bob, 0);
+            contract
+                .list_domain(name_id, 500, Currency::Native, None)
+                .unwrap();
+            contract.set_new_owner(name_id, a.charlie).unwrap();
+
+            let domain = contract.get_domain(name_id).unwrap();
+            assert_eq!(domain.default_address, a.charlie);
+            assert_eq!(domain.offer_state, State::NotOffering);
+            assert_eq!(
+                contract.name_to_owner.get(String::from("name.dot")),
+                Some(a.charlie)
+            );
+            assert_eq!(contract.get_owner_name_count(a.bob), 0);
+            assert_eq!(contract.get_owner_name_count(a.charlie), 1);
+
+            call(a.eve, 500);
+            assert!(contract.buy_domain(name_id, 500).is_err());
+        }
+
+        #[ink::test]
+        fn rented_names_stay_off_the_market() {
+            let a = accounts();
+            let mut contract = setup();
+            let name_id = register(&mut contract, "name.dot", a.bob);
+
+            call(a.bob, 0);
+            contract
+                .list_domain(name_id, 1_000, Currency::Native, None)
+                .unwrap();
+            contract.offer_rental(name_id, 10, DAY).unwrap();
+            call(a.charlie, 10);
+            contract.rent(name_id).unwrap();
+            assert_eq!(
+                contract.get_domain(name_id).unwrap().offer_state,
+                State::NotOffering
+            );
+            call(a.django, 1_000);
+            assert!(contract.buy_domain(name_id, 1_000).is_err());
+
+            // rental offers don't survive a sale
+            set_time(now() + DAY);
+            call(a.bob, 0);
+            contract.offer_rental(name_id, 10, DAY).unwrap();
+            contract
+                .list_domain(name_id, 1_000, Currency::Native, None)
+                .unwrap();
+            call(a.django, 1_000);
+            contract.buy_domain(name_id, 1_000).unwrap();
+            assert_eq!(contract.get_rental(name_id), None);
+        }
+
+        #[ink::test]
+        fn names_under_auction_or_lease_cannot_be_rented() {
+            let a = accounts();
+            let mut contract = setup();
+            let auctioned = register(&mut contract, "one.dot", a.bob);
+            let leased = register(&mut contract, "two.dot", a.bob);
+
+            call(a.bob, 0);
+            contract.offer_rental(auctioned, 10, DAY).unwrap();
+            contract.offer_rental(leased, 10, DAY).unwrap();
+            contract.start_auction(auctioned, 100, now() + DAY).unwrap();
+            contract
+                .offer_lease(leased, a.charlie, 100, 50, 2, DAY)
+                .unwrap();
+            // starting either drops the rental offer
+            assert_eq!(contract.get_rental(auctioned), None);
+            assert_eq!(contract.get_rental(leased), None);
+
+            contract.rentals.insert(
+                auctioned,
+                &Rental {
+                    price: 10,
+                    duration: DAY,
+                    renter: None,
+                    end_time: 0,
+                },
+            );
+            call(a.django, 10);
+            assert_eq!(contract.rent(auctioned), Err(DNSError::DomainInAuction));
+
+            call(a.charlie, 100);
+            contract.accept_lease(leased).unwrap();
+            contract.rentals.insert(
+                leased,
+                &Rental {
+                    price: 10,
+                    duration: DAY,
+                    renter: None,
+                    end_time: 0,
+                },
+            );
+            call(a.django, 10);
+            assert_eq!(contract.rent(leased), Err(DNSError::DomainLeased));
+        }
+
+        #[ink::test]
+        fn dutch_price_decays_along_its_curve() {
+            let linear = DutchListing {
+                start_price: 1_000,
+                floor_price: 200,
+                start_block: 10,
+                end_block: 110,
+                curve: PriceCurve::Linear,
+            };
+            assert_eq!(linear.price_at(10), 1_000);
+            assert_eq!(linear.price_at(35), 800);
+            assert_eq!(linear.price_at(60), 600);
+            assert_eq!(linear.price_at(110), 200);
+            assert_eq!(linear.price_at(500), 200);
+
+            let exponential = DutchListing {
+                curve: PriceCurve::Exponential { half_life: 10 },
+                ..linear
+            };
+            assert_eq!(exponential.price_at(10), 1_000);
+            // halfway through the first half life, a quarter of the premium is gone
+            assert_eq!(exponential.price_at(15), 800);
+            assert_eq!(exponential.price_at(20), 600);
+            assert_eq!(exponential.price_at(30), 400);
+            assert_eq!(exponential.price_at(109), 201);
+            assert_eq!(exponential.price_at(110), 200);
+
+            // large prices don't overflow
+            let large = DutchListing {
+                start_price: u128::MAX,
+                floor_price: 0,
+                start_block: 0,
+                end_block: 4,
+                curve: PriceCurve::Linear,
+            };
+            assert_eq!(large.price_at(2), u128::MAX - u128::MAX / 2);
+            let large = DutchListing {
+                curve: PriceCurve::Exponential { half_life: 4 },
+                end_block: 100,
+                ..large
+            };
+            assert_eq!(large.price_at(2), u128::MAX - u128::MAX / 4);
+        }
+
+        #[ink::test]
+        fn dutch_listing_refunds_overpayment() {
+            let a = accounts();
+            let mut contract = setup();
+            let name_id = register(&mut contract, "name.dot", a.bob);
+
+            call(a.bob, 0);
+            assert_eq!(
+                contract.create_dutch_listing(
+                    name_id,
+                    1_000,
+                    200,
+                    100,
+                    PriceCurve::Exponential { half_life: 0 }
+                ),
+                Err(DNSError::InvalidDutchListing)
+            );
+            contract
+                .create_dutch_listing(name_id, 1_000, 200, 100, PriceCurve::Linear)
+                .unwrap();
+            for _ in 0..50 {
+                test::advance_block::<E>();
+            }
+            assert_eq!(contract.quote_price(name_id), Ok(600));
+
+            let charlie_before = balance(a.charlie);
+            call(a.charlie, 1_000);
+            contract.buy_domain(name_id, 1_000).unwrap();
+            assert_eq!(balance(a.charlie) - charlie_before, 400);
+            assert_eq!(owner_of(&contract, name_id), a.charlie);
+        }
+
+        #[ink::test]
+        fn registration_accepts_only_plain_offer_states() {
+            let a = accounts();
+            let mut contract = setup();
+            let secret = [7; 32];
+            call(a.bob, 0);
+            let commitment = contract.make_commitment("name.dot".into(), a.bob, secret);
+            contract.commit(commitment).unwrap();
+            set_time(now() + MIN_COMMITMENT_AGE);
+
+            let dutch = State::DutchOffering(DutchListing {
+                start_price: 0,
+                floor_price: 1_000,
+                start_block: 0,
+                end_block: 10,
+                curve: PriceCurve::Exponential { half_life: 0 },
+            });
+            assert_eq!(
+                contract.create_new_dns(
+                    "name.dot".into(),
+                    secret,
+                    dutch,
+                    0,
+                    YEAR,
+                    Currency::Native
+                ),
+                Err(DNSError::InvalidOfferState)
+            );
+
+            // off-chain failed messages aren't rolled back, commit again
+            let secret = [8; 32];
+            let commitment = contract.make_commitment("name.dot".into(), a.bob, secret);
+            contract.commit(commitment).unwrap();
+            set_time(now() + MIN_COMMITMENT_AGE);
+            contract
+                .create_new_dns(
+                    "name.dot".into(),
+                    secret,
+                    State::PublicOffering,
+                    500,
+                    YEAR,
+                    Currency::Native,
+                )
+                .unwrap();
+        }
+
+        #[ink::test]
+        fn names_are_mapped_to_punycode() {
+            assert_eq!(normalize_name(" Example.DOT ").unwrap(), "example.dot");
+            assert_eq!(normalize_name("Bücher.dot").unwrap(), "xn--bcher-kva.dot");
+            assert_eq!(
+                normalize_name("例え。テスト").unwrap(),
+                "xn--r8jz45g.xn--zckzah"
+            );
+            assert_eq!(
+                normalize_name("xn--mnchen-3ya.dot").unwrap(),
+                "xn--mnchen-3ya.dot"
+            );
+            assert_eq!(display_name("xn--mnchen-3ya.dot"), "münchen.dot");
+            assert_eq!(normalize_name("xn--zz.dot"), Err(DNSError::InvalidIdn));
+            assert_eq!(normalize_name("a☃b.dot"), Err(DNSError::InvalidIdn));
+            // code points UTS-46 would map, and non-NFC forms, are rejected
+            assert_eq!(
+                normalize_name("ｅｘａｍｐｌｅ.dot"),
+                Err(DNSError::InvalidIdn)
+            );
+            assert_eq!(normalize_name("x².dot"), Err(DNSError::InvalidIdn));
+            assert_eq!(normalize_name("BÜCHER.dot"), Err(DNSError::InvalidIdn));
+            assert_eq!(normalize_name("straße.dot"), Err(DNSError::InvalidIdn));
+            assert_eq!(normalize_name("cafe\u{301}.dot"), Err(DNSError::InvalidIdn));
+            assert_eq!(normalize_name("café.dot").unwrap(), "xn--caf-dma.dot");
+            assert_eq!(normalize_name("xn--x-5ca.dot"), Err(DNSError::InvalidIdn));
+            // combining marks must follow a base and be in canonical order
+            assert_eq!(normalize_name("\u{301}a.dot"), Err(DNSError::InvalidIdn));
+            assert_eq!(
+                normalize_name("\u{5d1}\u{5bc}\u{5b8}.dot"),
+                Err(DNSError::InvalidIdn)
+            );
+            assert_eq!(
+                normalize_name("\u{5d1}\u{5b8}\u{5bc}.dot").unwrap(),
+                "xn--gdbi5d.dot"
+            );
+            assert_eq!(normalize_name("-ab.dot"), Err(DNSError::InvalidHyphen));
+            assert_eq!(normalize_name("a..dot"), Err(DNSError::EmptyLabel));
+        }
+
+        #[ink::test]
+        fn sealed_auction_charges_the_second_price() {
+            let a = accounts();
+            let mut contract = setup();
+            contract.set_price_schedule(vec![50]).unwrap();
+            let name = String::from("name.dot");
+            let salt = [3; 32];
+            // off-chain failed messages aren't rolled back, each attempt
+            // consumes a commitment
+            for secret in [[1; 32], [2; 32], [3; 32]] {
+                commit_to(&mut contract, &name, a.charlie, secret);
+            }
+            commit_to(&mut contract, &name, a.django, [3; 32]);
+            commit_to(&mut contract, &name, a.eve, [3; 32]);
+            set_time(now() + MIN_COMMITMENT_AGE);
+
+            call(a.charlie, 0);
+            let hash = contract.sealed_bid_hash(name.clone(), a.charlie, 300, salt);
+            assert_eq!(
+                contract.commit_sealed_bid(name.clone(), [1; 32], hash),
+                Err(DNSError::BidTooLow)
+            );
+            call(a.charlie, 49);
+            assert_eq!(
+                contract.commit_sealed_bid(name.clone(), [2; 32], hash),
+                Err(DNSError::BidTooLow)
+            );
+
+            let bids = [
+                (a.charlie, 300, 400),
+                (a.django, 200, 200),
+                (a.eve, 60, 100),
+            ];
+            for (bidder, bid, deposit) in bids {
+                call(bidder, deposit);
+                let hash = contract.sealed_bid_hash(name.clone(), bidder, bid, salt);
+                contract
+                    .commit_sealed_bid(name.clone(), [3; 32], hash)
+                    .unwrap();
+            }
+
+            let auction = contract.get_sealed_auction(name.clone()).unwrap();
+            set_time(auction.commit_end);
+            for (bidder, bid, _) in bids {
+                call(bidder, 0);
+                contract.reveal_sealed_bid(name.clone(), bid, salt).unwrap();
+            }
+
+            set_time(auction.reveal_end);
+            contract.finalize_sealed_auction(name.clone()).unwrap();
+
+            let name_id = contract.name_to_id.get(&name).unwrap();
+            assert_eq!(owner_of(&contract, name_id), a.charlie);
+            assert_eq!(contract.get_pending_return(a.charlie), 200);
+            assert_eq!(contract.get_pending_return(a.django), 200);
+            assert_eq!(contract.get_pending_return(a.eve), 100);
+            assert_eq!(contract.get_treasury(), 200);
+        }
+
+        #[ink::test]
+        fn sealed_auction_reserve_is_the_registration_price() {
+            let a = accounts();
+            let mut contract = setup();
+            contract.set_price_schedule(vec![50]).unwrap();
+            let salt = [3; 32];
+            commit_to(&mut contract, "one.dot", a.charlie, salt);
+            commit_to(&mut contract, "two.dot", a.django, salt);
+            set_time(now() + MIN_COMMITMENT_AGE);
+
+            // a lone bid pays the registration price
+            call(a.charlie, 100);
+            let hash = contract.sealed_bid_hash("one.dot".into(), a.charlie, 80, salt);
+            contract
+                .commit_sealed_bid("one.dot".into(), salt, hash)
+                .unwrap();
+            // a lone bid below the registration price wins nothing
+            call(a.django, 60);
+            let hash = contract.sealed_bid_hash("two.dot".into(), a.django, 10, salt);
+            contract
+                .commit_sealed_bid("two.dot".into(), salt, hash)
+                .unwrap();
+
+            let auction = contract.get_sealed_auction("one.dot".into()).unwrap();
+            set_time(auction.commit_end);
+            call(a.charlie, 0);
+            contract
+                .reveal_sealed_bid("one.dot".into(), 80, salt)
+                .unwrap();
+            call(a.django, 0);
+            contract
+                .reveal_sealed_bid("two.dot".into(), 10, salt)
+                .unwrap();
+
+            set_time(auction.reveal_end);
+            contract.finalize_sealed_auction("one.dot".into()).unwrap();
+            contract.finalize_sealed_auction("two.dot".into()).unwrap();
+
+            assert_eq!(contract.get_pending_return(a.charlie), 50);
+            assert_eq!(contract.get_pending_return(a.django), 60);
+            assert_eq!(contract.get_treasury(), 50);
+            assert!(contract.name_to_id.get(String::from("one.dot")).is_some());
+            assert!(contract.name_to_id.get(String::from("two.dot")).is_none());
+        }
+
+        #[ink::test]
+        fn sealed_bids_cannot_front_run_or_copy() {
+            let a = accounts();
+            let mut contract = setup();
+            let salt = [3; 32];
+
+            // a revealed registration can't be blocked by opening an auction
+            commit_to(&mut contract, "name.dot", a.bob, [7; 32]);
+            set_time(now() + MIN_COMMITMENT_AGE);
+            call(a.eve, 100);
+            let hash = contract.sealed_bid_hash("name.dot".into(), a.eve, 100, salt);
+            assert_eq!(
+                contract.commit_sealed_bid("name.dot".into(), [7; 32], hash),
+                Err(DNSError::NoCommitment)
+            );
+            call(a.bob, 0);
+            contract
+                .create_new_dns(
+                    "name.dot".into(),
+                    [7; 32],
+                    State::NotOffering,
+                    0,
+                    YEAR,
+                    Currency::Native,
+                )
+                .unwrap();
+
+            // a copied bid hash doesn't reveal for another bidder
+            commit_to(&mut contract, "other.dot", a.charlie, salt);
+            commit_to(&mut contract, "other.dot", a.eve, salt);
+            set_time(now() + MIN_COMMITMENT_AGE);
+            let hash = contract.sealed_bid_hash("other.dot".into(), a.charlie, 100, salt);
+            for bidder in [a.charlie, a.eve] {
+                call(bidder, 100);
+                contract
+                    .commit_sealed_bid("other.dot".into(), salt, hash)
+                    .unwrap();
+            }
+            let auction = contract.get_sealed_auction("other.dot".into()).unwrap();
+            set_time(auction.commit_end);
+            call(a.eve, 0);
+            assert_eq!(
+                contract.reveal_sealed_bid("other.dot".into(), 100, salt),
+                Err(DNSError::InvalidReveal)
+            );
+            call(a.charlie, 0);
+            contract
+                .reveal_sealed_bid("other.dot".into(), 100, salt)
+                .unwrap();
+        }
+
+        #[ink::test]
+        fn completed_lease_transfers_the_title() {
+            let a = accounts();
+            let mut contract = setup();
+            let name_id = register(&mut contract, "name.dot", a.bob);
+
+            call(a.bob, 0);
+            contract
+                .offer_lease(name_id, a.charlie, 100, 50, 2, DAY)
+                .unwrap();
+            call(a.charlie, 100);
+            contract.accept_lease(name_id).unwrap();
+
+            // the lessee controls resolution, the seller keeps the title
+            assert_eq!(owner_of(&contract, name_id), a.bob);
+            assert_eq!(contract.get_controller(name_id), Some(a.charlie));
+            call(a.charlie, 0);
+            contract.set_resolved_address(name_id, a.django).unwrap();
+            assert_eq!(contract.resolve("name.dot".into()), Some(a.django));
+            assert_eq!(contract.delist_domain(name_id), Err(DNSError::NotAOwner));
+            assert_eq!(
+                contract.create_subdomain(name_id, "api".into(), a.charlie),
+                Err(DNSError::NotAOwner)
+            );
+
+            let bob_before = balance(a.bob);
+            call(a.charlie, 50);
+            contract.pay_installment(name_id).unwrap();
+            set_time(now() + DAY);
+            contract.pay_installment(name_id).unwrap();
+
+            assert_eq!(balance(a.bob) - bob_before, 200);
+            assert_eq!(owner_of(&contract, name_id), a.charlie);
+            assert_eq!(contract.get_lease(name_id), None);
+            assert_eq!(contract.get_last_sale_price(name_id), Some(200));
+            assert_eq!(contract.get_owner_name_count(a.bob), 0);
+            assert_eq!(contract.get_owner_name_count(a.charlie), 1);
+        }
+
+        #[ink::test]
+        fn defaulted_lease_returns_the_name_to_the_seller() {
+            let a = accounts();
+            let mut contract = setup();
+            let name_id = register(&mut contract, "name.dot", a.bob);
+
+            call(a.bob, 0);
+            contract
+                .offer_lease(name_id, a.charlie, 100, 50, 2, DAY)
+                .unwrap();
+            call(a.charlie, 100);
+            contract.accept_lease(name_id).unwrap();
+
+            call(a.bob, 0);
+            assert_eq!(
+                contract.reclaim_lease(name_id),
+                Err(DNSError::PaymentNotOverdue)
+            );
+
+            set_time(now() + DAY + 1);
+            call(a.charlie, 50);
+            assert_eq!(
+                contract.pay_installment(name_id),
+                Err(DNSError::LeaseDefaulted)
+            );
+
+            call(a.bob, 0);
+            contract.reclaim_lease(name_id).unwrap();
+            assert_eq!(owner_of(&contract, name_id), a.bob);
+            assert_eq!(contract.get_pending_return(a.bob), 100);
+            assert_eq!(contract.get_lease(name_id), None);
+        }
+
+        #[ink::test]
+        fn lapsed_lease_refunds_the_buyer() {
+            let a = accounts();
+            let mut contract = setup();
+            let name_id = register(&mut contract, "name.dot", a.bob);
+            let expires_at = contract.get_domain(name_id).unwrap().expires_at;
+
+            call(a.bob, 0);
+            contract
+                .offer_lease(name_id, a.charlie, 100, 50, 1, 2 * YEAR)
+                .unwrap();
+            call(a.charlie, 100);
+            contract.accept_lease(name_id).unwrap();
+
+            // the registration lapses before the installment is due
+            set_time(expires_at);
+            call(a.charlie, 50);
+            assert_eq!(
+                contract.pay_installment(name_id),
+                Err(DNSError::DomainExpired)
+            );
+            call(a.django, 0);
+            assert_eq!(contract.reclaim_lease(name_id), Err(DNSError::NotAOwner));
+
+            call(a.charlie, 0);
+            contract.reclaim_lease(name_id).unwrap();
+            assert_eq!(contract.get_pending_return(a.charlie), 100);
+            assert_eq!(contract.get_pending_return(a.bob), 0);
+            assert_eq!(contract.get_lease(name_id), None);
+        }
+
+        #[ink::test]
+        fn private_offer_is_only_sold_to_the_designated_buyer() {
+            let a = accounts();
+            let mut contract = setup();
+            let name_id = register(&mut contract, "name.dot", a.bob);
+
+            call(a.bob, 0);
+            contract
+                .create_private_offer(name_id, a.charlie, 100, Currency::Native, None)
+                .unwrap();
+
+            call(a.django, 100);
+            assert_eq!(
+                contract.buy_domain(name_id, 100),
+                Err(DNSError::NotDesignatedBuyer)
+            );
+            assert_eq!(owner_of(&contract, name_id), a.bob);
+
+            call(a.charlie, 100);
+            contract.buy_domain(name_id, 100).unwrap();
+            assert_eq!(owner_of(&contract, name_id), a.charlie);
+        }
+
+        #[ink::test]
+        fn english_auction_refunds_outbid_and_pays_seller() {
+            let a = accounts();
+            let mut contract = setup();
+            contract.set_fee(1_000).unwrap();
+            let name_id = register(&mut contract, "name.dot", a.bob);
+
+            call(a.bob, 0);
+            let end_time = now() + DAY;
+            contract.start_auction(name_id, 100, end_time).unwrap();
+
+            call(a.charlie, 200);
+            contract.bid(name_id).unwrap();
+            call(a.django, 150);
+            assert_eq!(contract.bid(name_id), Err(DNSError::BidTooLow));
+            call(a.django, 300);
+            contract.bid(name_id).unwrap();
+            assert_eq!(contract.get_pending_return(a.charlie), 200);
+
+            // a late bid extends the auction
+            set_time(end_time - 60 * 1000);
+            call(a.eve, 400);
+            contract.bid(name_id).unwrap();
+            let extended = contract.get_auction(name_id).unwrap().end_time;
+            assert_eq!(extended, now() + AUCTION_EXTENSION);
+            assert_eq!(contract.get_pending_return(a.django), 300);
+
+            set_time(end_time);
+            assert_eq!(
+                contract.settle_auction(name_id),
+                Err(DNSError::AuctionNotEnded)
+            );
+
+            set_time(extended);
+            let bob_before = balance(a.bob);
+            contract.settle_auction(name_id).unwrap();
+            assert_eq!(balance(a.bob) - bob_before, 360);
+            assert_eq!(contract.get_treasury(), 40);
+            assert_eq!(owner_of(&contract, name_id), a.eve);
+        }
+
+        #[ink::test]
+        fn standing_bid_is_accepted_at_the_seen_amount() {
+            let a = accounts();
+            let mut contract = setup();
+            let name_id = register(&mut contract, "name.dot", a.bob);
+
+            call(a.charlie, 100);
+            contract.place_standing_bid(name_id, now() + DAY).unwrap();
+            // replacing the bid refunds the previous one
+            call(a.charlie, 1);
+            contract.place_standing_bid(name_id, now() + DAY).unwrap();
+            assert_eq!(contract.get_pending_return(a.charlie), 100);
+
+            call(a.bob, 0);
+            assert_eq!(
+                contract.accept_standing_bid(name_id, a.charlie, 100),
+                Err(DNSError::PriceMismatch)
+            );
+
+            // off-chain failed messages aren't rolled back, accept another bid
+            call(a.django, 5);
+            contract.place_standing_bid(name_id, now() + DAY).unwrap();
+            call(a.bob, 0);
+            contract.accept_standing_bid(name_id, a.django, 5).unwrap();
+            assert_eq!(owner_of(&contract, name_id), a.django);
+        }
+
+        #[ink::test]
+        fn full_bid_book_takes_higher_and_drops_expired_bids() {
+            let a = accounts();
+            let mut contract = setup();
+            let name_id = register(&mut contract, "name.dot", a.bob);
+            let expires_at = now() + DAY;
+
+            for index in 0..MAX_STANDING_BIDS as u8 {
+                call(AccountId::from([100 + index; 32]), 10 + index as u128);
+                contract.place_standing_bid(name_id, expires_at).unwrap();
+            }
+
+            call(a.charlie, 10);
+            assert_eq!(
+                contract.place_standing_bid(name_id, expires_at),
+                Err(DNSError::BidBookFull)
+            );
+            call(a.charlie, 11);
+            contract.place_standing_bid(name_id, expires_at).unwrap();
+            assert_eq!(contract.get_pending_return(AccountId::from([100; 32])), 10);
+
+            set_time(expires_at);
+            call(a.django, 1);
+            contract.place_standing_bid(name_id, now() + DAY).unwrap();
+            assert_eq!(contract.get_standing_bids(name_id).len(), 1);
+            assert_eq!(contract.get_pending_return(a.charlie), 11);
+        }
+
+        #[ink::test]
+        fn sale_splits_fee_royalty_and_seller_proceeds() {
+            let a = accounts();
+            let mut contract = setup();
+            contract.set_fee(1_000).unwrap();
+            let name_id = register(&mut contract, "name.dot", a.bob);
+
+            call(a.bob, 0);
+            contract.set_royalty(name_id, 500).unwrap();
+            contract
+                .list_domain(name_id, 1_000, Currency::Native, None)
+                .unwrap();
+
+            // first sale by the registrant carries no royalty
+            let bob_before = balance(a.bob);
+            call(a.charlie, 1_000);
+            contract.buy_domain(name_id, 1_000).unwrap();
+            assert_eq!(balance(a.bob) - bob_before, 900);
+            assert_eq!(contract.get_treasury(), 100);
+
+            // the registrant may lower but not raise the royalty of a resold name
+            call(a.bob, 0);
+            assert_eq!(
+                contract.set_royalty(name_id, 600),
+                Err(DNSError::InvalidRoyalty)
+            );
+
+            call(a.charlie, 0);
+            contract
+                .list_domain(name_id, 10_000, Currency::Native, None)
+                .unwrap();
+            let bob_before = balance(a.bob);
+            let charlie_before = balance(a.charlie);
+            call(a.django, 10_000);
+            assert_eq!(
+                contract.buy_domain(name_id, 9_999),
+                Err(DNSError::PriceMismatch)
+            );
+            contract.buy_domain(name_id, 10_000).unwrap();
+
+            assert_eq!(balance(a.bob) - bob_before, 500);
+            assert_eq!(balance(a.charlie) - charlie_before, 8_500);
+            assert_eq!(contract.get_treasury(), 1_100);
+            assert_eq!(owner_of(&contract, name_id), a.django);
+            assert_eq!(contract.get_last_sale_price(name_id), Some(10_000));
+            assert_eq!(contract.get_sale_history(name_id, 0, 10).len(), 2);
+            assert_eq!(contract.get_owner_name_count(a.charlie), 0);
+            assert_eq!(contract.get_owner_name_count(a.django), 1);
+        }
+
+        #[ink::test]
+        fn lapsed_listing_is_off_the_market() {
+            let a = accounts();
+            let mut contract = setup();
+            let name_id = register(&mut contract, "name.dot", a.bob);
+
+            call(a.bob, 0);
+            contract
+                .list_domain(name_id, 100, Currency::Native, Some(now() + DAY))
+                .unwrap();
+            assert_eq!(
+                contract.get_domain(name_id).unwrap().offer_state,
+                State::PublicOffering
+            );
+
+            set_time(now() + DAY);
+            assert_eq!(
+                contract.get_domain(name_id).unwrap().offer_state,
+                State::NotOffering
+            );
+            call(a.charlie, 100);
+            assert_eq!(
+                contract.buy_domain(name_id, 100),
+                Err(DNSError::OfferExpired)
+            );
+            assert_eq!(owner_of(&contract, name_id), a.bob);
+        }
+
+        #[ink::test]
+        fn bundle_is_bought_whole_or_not_at_all() {
+            let a = accounts();
+            let mut contract = setup();
+            let one = register(&mut contract, "one.dot", a.bob);
+            let two = register(&mut contract, "two.dot", a.bob);
+            let three = register(&mut contract, "three.dot", a.bob);
+
+            call(a.bob, 0);
+            let stale = contract.create_bundle(vec![one, two], 100).unwrap();
+            let bundle = contract.create_bundle(vec![one, three], 101).unwrap();
+            contract.set_new_owner(two, a.eve).unwrap();
+
+            call(a.charlie, 100);
+            assert_eq!(
+                contract.buy_bundle(stale),
+                Err(DNSError::BundleMemberChanged)
+            );
+            assert_eq!(owner_of(&contract, one), a.bob);
+
+            let bob_before = balance(a.bob);
+            call(a.charlie, 101);
+            contract.buy_bundle(bundle).unwrap();
+            assert_eq!(owner_of(&contract, one), a.charlie);
+            assert_eq!(owner_of(&contract, three), a.charlie);
+            assert_eq!(balance(a.bob) - bob_before, 101);
+            assert_eq!(contract.get_last_sale_price(one), Some(50));
+            assert_eq!(contract.get_last_sale_price(three), Some(51));
+            assert_eq!(contract.get_bundle(bundle), None);
+        }
+
+        #[ink::test]
+        fn negotiation_prices_are_pinned() {
+            let a = accounts();
+            let mut contract = setup();
+            let name_id = register(&mut contract, "name.dot", a.bob);
+
+            call(a.charlie, 100);
+            contract.propose_price(name_id).unwrap();
+            call(a.charlie, 1);
+            contract.propose_price(name_id).unwrap();
+
+            call(a.bob, 0);
+            assert_eq!(
+                contract.accept_proposal(name_id, a.charlie, 100),
+                Err(DNSError::PriceMismatch)
+            );
+
+            // off-chain failed messages aren't rolled back, so each case
+            // below negotiates with a different buyer
+            call(a.django, 200);
+            contract.propose_price(name_id).unwrap();
+            call(a.bob, 0);
+            contract.counter_offer(name_id, a.django, 500).unwrap();
+            call(a.django, 300);
+            assert_eq!(
+                contract.accept_counter_offer(name_id, 400),
+                Err(DNSError::PriceMismatch)
+            );
+
+            // a counter offer lapses when the name changes hands
+            call(a.frank, 200);
+            contract.propose_price(name_id).unwrap();
+            call(a.bob, 0);
+            contract.counter_offer(name_id, a.frank, 500).unwrap();
+            contract.set_new_owner(name_id, a.eve).unwrap();
+            call(a.frank, 300);
+            assert_eq!(
+                contract.accept_counter_offer(name_id, 500),
+                Err(DNSError::NoCounterOffer)
+            );
+
+            let buyer = AccountId::from([0x42; 32]);
+            call(buyer, 7);
+            contract.propose_price(name_id).unwrap();
+            call(a.eve, 0);
+            contract.accept_proposal(name_id, buyer, 7).unwrap();
+            assert_eq!(owner_of(&contract, name_id), buyer);
+        }
+
+        #[ink::test]
+        fn expired_names_pass_through_grace_and_redemption() {
+            let a = accounts();
+            let mut contract = setup();
+            contract
+                .set_expiry_periods(DEFAULT_GRACE_PERIOD, DEFAULT_REDEMPTION_PERIOD, 1_000)
+                .unwrap();
+            let name_id = register(&mut contract, "name.dot", a.bob);
+            let expires_at = contract.get_domain(name_id).unwrap().expires_at;
+
+            set_time(expires_at);
+            assert_eq!(
+                contract.get_name_phase(name_id),
+                Some(NamePhase::GracePeriod)
+            );
+            call(a.bob, 0);
+            assert_eq!(
+                contract.list_domain(name_id, 1, Currency::Native, None),
+                Err(DNSError::DomainExpired)
+            );
+            call(a.charlie, 0);
+            assert_eq!(
+                contract.renew(name_id, YEAR, Currency::Native),
+                Err(DNSError::NotAOwner)
+            );
+            call(a.bob, 0);
+            contract.renew(name_id, YEAR, Currency::Native).unwrap();
+            assert_eq!(contract.get_name_phase(name_id), Some(NamePhase::Active));
+
+            let expires_at = expires_at + YEAR;
+            set_time(expires_at + DEFAULT_GRACE_PERIOD);
+            assert_eq!(
+                contract.get_name_phase(name_id),
+                Some(NamePhase::Redemption)
+            );
+            assert_eq!(
+                contract.renew(name_id, YEAR, Currency::Native),
+                Err(DNSError::IncorrectPayment)
+            );
+            call(a.bob, 1_000);
+            contract.renew(name_id, YEAR, Currency::Native).unwrap();
+            assert_eq!(contract.get_treasury(), 1_000);
+
+            let expires_at = expires_at + YEAR;
+            set_time(expires_at + DEFAULT_GRACE_PERIOD + DEFAULT_REDEMPTION_PERIOD);
+            assert_eq!(contract.get_name_phase(name_id), Some(NamePhase::Available));
+            let new_id = register(&mut contract, "name.dot", a.charlie);
+            assert_ne!(new_id, name_id);
+            assert_eq!(owner_of(&contract, new_id), a.charlie);
+            assert_eq!(contract.get_owner_name_count(a.bob), 0);
+        }
+
+        #[ink::test]
+        fn registration_in_tokens_needs_a_rate() {
+            let a = accounts();
+            let mut contract = setup();
+            let token = a.frank;
+            contract.set_price_schedule(vec![50]).unwrap();
+            contract.set_token_accepted(token, true).unwrap();
+
+            let currency = Currency::Psp22(token);
+            assert_eq!(
+                contract.quote_registration_in("name.dot".into(), YEAR, currency),
+                Err(DNSError::NoTokenRate)
+            );
+            contract
+                .set_token_rate(token, Some(2 * RATE_DENOMINATOR))
+                .unwrap();
+            assert_eq!(
+                contract.quote_registration_in("name.dot".into(), YEAR, currency),
+                Ok(100)
+            );
+        }
+
+        #[ink::test]
+        fn registration_requires_a_matured_commitment() {
+            let a = accounts();
+            let mut contract = setup();
+            let secret = [1; 32];
+
+            call(a.bob, 0);
+            assert_eq!(
+                contract.create_new_dns(
+                    "name.dot".into(),
+                    secret,
+                    State::NotOffering,
+                    0,
+                    YEAR,
+                    Currency::Native
+                ),
+                Err(DNSError::NoCommitment)
+            );
+
+            let commitment = contract.make_commitment("name.dot".into(), a.bob, secret);
+            contract.commit(commitment).unwrap();
+            assert_eq!(
+                contract.create_new_dns(
+                    "name.dot".into(),
+                    secret,
+                    State::NotOffering,
+                    0,
+                    YEAR,
+                    Currency::Native
+                ),
+                Err(DNSError::CommitmentTooNew)
+            );
+        }
+
+        #[ink::test]
+        fn reserved_names_are_assigned_by_the_owner() {
+            let a = accounts();
+            let mut contract = setup();
+
+            call(a.bob, 0);
+            assert_eq!(
+                contract.reserve_names(vec!["gold.dot".into()]),
+                Err(DNSError::CallerIsNotOwner)
+            );
+            call(a.alice, 0);
+            contract
+                .reserve_names(vec!["Gold.dot".into(), "silver.dot".into()])
+                .unwrap();
+            assert!(contract.is_reserved("gold.dot".into()));
+            assert_eq!(
+                try_register(&mut contract, "gold.dot", a.bob),
+                Err(DNSError::NameReserved)
+            );
+
+            call(a.alice, 0);
+            let name_id = contract
+                .assign_reserved_name("gold.dot".into(), a.bob, YEAR)
+                .unwrap();
+            assert_eq!(owner_of(&contract, name_id), a.bob);
+            assert!(!contract.is_reserved("gold.dot".into()));
+            assert_eq!(
+                contract.assign_reserved_name("bronze.dot".into(), a.bob, YEAR),
+                Err(DNSError::NameNotReserved)
+            );
+
+            contract.unreserve_names(vec!["silver.dot".into()]).unwrap();
+            assert!(!contract.is_reserved("silver.dot".into()));
+            register(&mut contract, "silver.dot", a.charlie);
+        }
+
+        #[ink::test]
+        fn tld_policy_limits_registrations() {
+            let a = accounts();
+            let mut contract = setup();
+            let policy = TldPolicy {
+                price_schedule: None,
+                min_length: 3,
+                max_length: 5,
+                open: true,
+                manager: Some(a.bob),
+            };
+            contract.create_tld("Web".into(), policy.clone()).unwrap();
+            assert_eq!(contract.get_tlds(), vec![String::from("dot"), "web".into()]);
+            assert_eq!(
+                contract.create_tld("web".into(), policy.clone()),
+                Err(DNSError::TldExists)
+            );
+
+            assert_eq!(
+                try_register(&mut contract, "name.none", a.charlie),
+                Err(DNSError::TldNotFound)
+            );
+            assert_eq!(
+                try_register(&mut contract, "ab.web", a.charlie),
+                Err(DNSError::LabelLengthNotAllowed)
+            );
+            assert_eq!(
+                try_register(&mut contract, "abcdef.web", a.charlie),
+                Err(DNSError::LabelLengthNotAllowed)
+            );
+            register(&mut contract, "abc.web", a.charlie);
+
+            // the manager may change the policy but not the manager
+            call(a.bob, 0);
+            let closed = TldPolicy {
+                open: false,
+                ..policy.clone()
+            };
+            contract
+                .set_tld_policy("web".into(), closed.clone())
+                .unwrap();
+            assert_eq!(
+                contract.set_tld_policy(
+                    "web".into(),
+                    TldPolicy {
+                        manager: Some(a.charlie),
+                        ..closed.clone()
+                    }
+                ),
+                Err(DNSError::CallerIsNotOwner)
+            );
+            call(a.charlie, 0);
+            assert_eq!(
+                contract.set_tld_policy("web".into(), policy),
+                Err(DNSError::CallerIsNotOwner)
+            );
+
+            assert_eq!(
+                try_register(&mut contract, "abcd.web", a.charlie),
+                Err(DNSError::TldClosed)
+            );
+            assert_eq!(contract.get_tld("WEB".into()), Some(closed));
+        }
+
+        #[ink::test]
+        fn subdomains_follow_their_parent() {
+            let a = accounts();
+            let mut contract = setup();
+            let parent_id = register(&mut contract, "example.dot", a.bob);
+
+            call(a.bob, 0);
+            let own_id = contract
+                .create_subdomain(parent_id, "api".into(), a.bob)
+                .unwrap();
+            let given_id = contract
+                .create_subdomain(own_id, "v1".into(), a.charlie)
+                .unwrap();
+            assert_eq!(contract.get_subdomains(parent_id), vec![own_id, given_id]);
+            assert_eq!(
+                contract.get_domain(given_id).unwrap().name,
+                "v1.api.example.dot"
+            );
+
+            contract.set_new_owner(parent_id, a.django).unwrap();
+            assert_eq!(owner_of(&contract, own_id), a.django);
+            assert_eq!(owner_of(&contract, given_id), a.charlie);
+
+            call(a.django, 0);
+            assert_eq!(
+                contract.renew(own_id, YEAR, Currency::Native),
+                Err(DNSError::IsSubdomain)
+            );
+            contract.renew(parent_id, YEAR, Currency::Native).unwrap();
+            let expires_at = contract.get_domain(parent_id).unwrap().expires_at;
+            assert_eq!(
+                contract.get_domain(given_id).unwrap().expires_at,
+                expires_at
+            );
+        }
+
+        #[ink::test]
+        fn registrar_sells_and_renews_subdomains() {
+            let a = accounts();
+            let mut contract = setup();
+            contract.set_fee(1_000).unwrap();
+            let parent_id = register(&mut contract, "example.dot", a.bob);
+
+            call(a.charlie, 100);
+            assert_eq!(
+                contract.register_subdomain(parent_id, "shop".into()),
+                Err(DNSError::RegistrarClosed)
+            );
+
+            call(a.bob, 0);
+            contract
+                .set_subdomain_registrar(
+                    parent_id,
+                    SubdomainRegistrar {
+                        price: 100,
+                        duration: DAY,
+                        min_length: 3,
+                        max_length: 10,
+                    },
+                )
+                .unwrap();
+
+            call(a.charlie, 100);
+            assert_eq!(
+                contract.register_subdomain(parent_id, "ab".into()),
+                Err(DNSError::LabelLengthNotAllowed)
+            );
+            let name_id = contract
+                .register_subdomain(parent_id, "shop".into())
+                .unwrap();
+            assert_eq!(owner_of(&contract, name_id), a.charlie);
+            assert_eq!(contract.get_pending_return(a.bob), 90);
+            assert_eq!(contract.get_treasury(), 10);
+            let expires_at = contract.get_domain(name_id).unwrap().expires_at;
+            assert_eq!(expires_at, now() + DAY);
+
+            // a lapsed subdomain can be renewed by its owner
+            set_time(expires_at);
+            call(a.django, 100);
+            assert_eq!(contract.renew_subdomain(name_id), Err(DNSError::NotAOwner));
+            call(a.charlie, 100);
+            contract.renew_subdomain(name_id).unwrap();
+            assert_eq!(
+                contract.get_domain(name_id).unwrap().expires_at,
+                expires_at + DAY
+            );
+            assert_eq!(contract.get_pending_return(a.bob), 180);
+        }
+    }
 }